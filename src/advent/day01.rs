@@ -1,51 +1,299 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
-use std::{collections::VecDeque, error::Error};
+use rand::Rng;
+use std::{
+    cmp::Ordering,
+    collections::VecDeque,
+    error::Error,
+    io::{self, Write},
+    ops::Deref,
+};
+use structopt::StructOpt;
+use strum::{Display, EnumString};
 
-/// Executes the exercise of day 01
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
-) -> Result<[u64; 2], Box<dyn Error>> {
-    pin_mut!(input);
+// Options of the sonar sweep analysis. Not a doc comment, as it is flattened into the
+// options of the command line.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    /// Additionally analyze the sonar sweep of day 01 with a sliding window of this length
+    #[structopt(long)]
+    pub sonar_window: Option<usize>,
 
-    // Iterate over each line and calculate metrics
-    let mut previous = (None, None);
-    let mut sum = 0;
-    let mut count = (0, 0);
-    let mut window = VecDeque::new();
-    while let Some(line) = input.try_next().await? {
-        // parse line value
-        let line: u64 = line.parse()?;
+    /// Aggregate of the depths within the sliding window: sum, mean, min, max or median
+    #[structopt(long, default_value = "sum")]
+    pub sonar_aggregate: Aggregate,
+}
+
+/// How the depths within a sliding window are combined into a single value
+#[derive(Debug, Default, Clone, Copy, PartialEq, EnumString, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Mean,
+    Min,
+    Max,
+    Median,
+}
 
-        // check if value of line has increased
-        if let Some(previous) = previous.0 {
-            if line > previous {
-                count.0 += 1;
+/// The largest change of the aggregate between two positions of the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Jump {
+    /// Line of the depth, which moved the window onto its new position
+    line: usize,
+    from: f64,
+    to: f64,
+}
+
+/// How the aggregate of the window changed while it slid over the depths
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Summary {
+    increases: u64,
+    decreases: u64,
+    plateaus: u64,
+    largest_jump: Option<Jump>,
+}
+
+/// Sliding window over the depths, which only keeps the depths of its current position
+struct SlidingWindow {
+    length: usize,
+    aggregate: Aggregate,
+    window: VecDeque<u64>,
+    sum: u64,
+    line: usize,
+    previous: Option<f64>,
+    summary: Summary,
+}
+
+impl SlidingWindow {
+    /// Create a window of the given length, which must be at least 1
+    fn new(length: usize, aggregate: Aggregate) -> Self {
+        assert!(
+            length > 0,
+            "the sliding window needs a length of at least 1"
+        );
+        Self {
+            length,
+            aggregate,
+            window: VecDeque::with_capacity(length + 1),
+            sum: 0,
+            line: 0,
+            previous: None,
+            summary: Summary::default(),
+        }
+    }
+
+    /// Get the aggregate of the full window
+    fn value(&self) -> f64 {
+        match self.aggregate {
+            Aggregate::Sum => self.sum as f64,
+            Aggregate::Mean => self.sum as f64 / self.length as f64,
+            Aggregate::Min => *self.window.iter().min().unwrap() as f64,
+            Aggregate::Max => *self.window.iter().max().unwrap() as f64,
+            Aggregate::Median => {
+                let mut sorted: Vec<_> = self.window.iter().copied().collect();
+                sorted.sort_unstable();
+                let mid = sorted.len() / 2;
+                if sorted.len() % 2 == 0 {
+                    (sorted[mid - 1] + sorted[mid]) as f64 / 2.0
+                } else {
+                    sorted[mid] as f64
+                }
             }
         }
-        previous.0 = Some(line);
+    }
+
+    /// Slide the window over the next depth and compare its aggregate with the previous one
+    fn push(&mut self, depth: u64) {
+        self.line += 1;
+        self.window.push_back(depth);
+        self.sum += depth;
+        if self.window.len() > self.length {
+            self.sum -= self.window.pop_front().unwrap();
+        } else if self.window.len() < self.length {
+            return;
+        }
 
-        // calculate sum of sliding window and put values into a queue
-        // for a more efficient calculation
-        window.push_back(line);
-        sum += line;
-        match window.len().cmp(&3) {
-            std::cmp::Ordering::Greater => {
-                sum -= window.pop_front().unwrap();
+        let value = self.value();
+        if let Some(previous) = self.previous {
+            let summary = &mut self.summary;
+            match value.partial_cmp(&previous) {
+                Some(Ordering::Greater) => summary.increases += 1,
+                Some(Ordering::Less) => summary.decreases += 1,
+                _ => summary.plateaus += 1,
             }
-            std::cmp::Ordering::Less => {
-                continue;
+
+            let jump = (value - previous).abs();
+            if summary
+                .largest_jump
+                .is_none_or(|largest| jump > (largest.to - largest.from).abs())
+            {
+                summary.largest_jump = Some(Jump {
+                    line: self.line,
+                    from: previous,
+                    to: value,
+                });
             }
-            _ => {}
         }
+        self.previous = Some(value);
+    }
+}
 
-        // check if sum of window has increased
-        if let Some(previous) = previous.1 {
-            if sum > previous {
-                count.1 += 1;
-            }
+/// Executes the exercise of day 01
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
+) -> Result<[u64; 2], Box<dyn Error>> {
+    pin_mut!(input);
+
+    // the parts compare single depths and the sums of three depths
+    let mut windows = vec![
+        SlidingWindow::new(1, Aggregate::Sum),
+        SlidingWindow::new(3, Aggregate::Sum),
+    ];
+    if let Some(length) = options.sonar_window {
+        if length == 0 {
+            return Err("the sliding window needs a length of at least 1".into());
         }
-        previous.1 = Some(sum);
+        windows.push(SlidingWindow::new(length, options.sonar_aggregate));
     }
 
-    Ok([count.0, count.1])
+    // Iterate over each line and slide all windows over it
+    while let Some(line) = input.try_next().await? {
+        let depth: u64 = line.parse()?;
+        for window in &mut windows {
+            window.push(depth);
+        }
+    }
+
+    // report the additional analysis
+    if let Some(window) = windows.get(2) {
+        let summary = window.summary;
+        println!(
+            "Sonar sweep with the {} of {} depths: {} increases, {} decreases, {} plateaus",
+            window.aggregate, window.length, summary.increases, summary.decreases, summary.plateaus
+        );
+        if let Some(jump) = summary.largest_jump {
+            println!(
+                "Largest jump from {} to {} at line {}",
+                jump.from, jump.to, jump.line
+            );
+        }
+    }
+
+    Ok([windows[0].summary.increases, windows[1].summary.increases])
+}
+
+/// Generate a sonar sweep report of `size` depths, which change by up to `scale`
+/// between two measurements
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let scale = options.scale.unwrap_or(10) as i64;
+
+    // random walk into the deep, which never rises above the surface
+    let mut depth = rng.gen_range(100..200);
+    for _ in 0..options.size {
+        writeln!(output, "{}", depth)?;
+        depth = (depth + rng.gen_range(-scale..=2 * scale)).max(0);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+
+    const EXAMPLE: &str = "\
+199
+200
+208
+210
+200
+207
+240
+269
+260
+263
+";
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines(EXAMPLE), &Options::default()).await.unwrap(),
+            [7, 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn shorter_than_window() {
+        assert_eq!(
+            execute(lines("3\n1\n"), &Options::default()).await.unwrap(),
+            [0, 0]
+        );
+    }
+
+    /// Helper to slide a window over the given depths
+    fn slide(length: usize, aggregate: Aggregate, depths: &[u64]) -> Summary {
+        let mut window = SlidingWindow::new(length, aggregate);
+        for &depth in depths {
+            window.push(depth);
+        }
+        window.summary
+    }
+
+    #[test]
+    fn counts_decreases_and_plateaus() {
+        let summary = slide(1, Aggregate::Sum, &[3, 3, 5, 2, 2, 2]);
+        assert_eq!(
+            (summary.increases, summary.decreases, summary.plateaus),
+            (1, 1, 3)
+        );
+    }
+
+    #[test]
+    fn aggregates() {
+        let depths = [1, 9, 2, 8, 8, 3];
+        let values = |aggregate| {
+            let mut window = SlidingWindow::new(4, aggregate);
+            depths
+                .iter()
+                .filter_map(|&depth| {
+                    window.push(depth);
+                    window.previous
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(Aggregate::Sum), [20.0, 27.0, 21.0]);
+        assert_eq!(values(Aggregate::Mean), [5.0, 6.75, 5.25]);
+        assert_eq!(values(Aggregate::Min), [1.0, 2.0, 2.0]);
+        assert_eq!(values(Aggregate::Max), [9.0, 9.0, 8.0]);
+        assert_eq!(values(Aggregate::Median), [5.0, 8.0, 5.5]);
+    }
+
+    #[test]
+    fn largest_jump() {
+        let summary = slide(2, Aggregate::Max, &[4, 1, 7, 2, 1, 1, 0]);
+        assert_eq!(
+            summary.largest_jump,
+            Some(Jump {
+                line: 5,
+                from: 7.0,
+                to: 2.0
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_empty_window() {
+        let options = Options {
+            sonar_window: Some(0),
+            ..Default::default()
+        };
+        assert!(execute(lines(EXAMPLE), &options).await.is_err());
+    }
 }
@@ -1,6 +1,41 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
 use logos::Logos;
-use std::error::Error;
+use rand::Rng;
+use snafu::{OptionExt, Snafu};
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Deref,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+// Options of the submarine. Not a doc comment, as it is flattened into the options of
+// the command line.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    /// Export the course of both navigation models of day 02 as CSV to this file
+    #[structopt(long, parse(from_os_str))]
+    pub submarine_trajectory: Option<PathBuf>,
+}
+
+/// The errors of invalid commands
+#[derive(Debug, Snafu)]
+enum CommandError {
+    #[snafu(display("Unknown command {:?} in line {}", command, line))]
+    UnknownCommand { line: usize, command: String },
+
+    #[snafu(display("Missing or invalid parameter of the command in line {}", line))]
+    MissingParameter { line: usize },
+
+    #[snafu(display("Unexpected input {:?} after the command in line {}", input, line))]
+    TrailingInput { line: usize, input: String },
+
+    #[snafu(display("The submarine leaves the range of 64 bit numbers in line {}", line))]
+    Overflow { line: usize },
+}
 
 /// The commands expected in out input
 #[derive(Logos, Debug, PartialEq)]
@@ -15,7 +50,7 @@ enum Command {
     Up,
 
     #[regex("[0-9]+", |lex| lex.slice().parse())]
-    Number(u32),
+    Number(i64),
 
     #[regex(" +", logos::skip)]
     Whitespace,
@@ -27,67 +62,292 @@ enum Command {
 /// A submarine
 #[derive(Default)]
 struct Submarine {
-    position: u32,
-    depth: u32,
-    aim: u32,
+    position: i64,
+    depth: i64,
+    aim: i64,
 }
 
 impl Submarine {
-    /// Execute the command of part 1
-    fn execute_command_1(&mut self, command: &Command, param: u32) {
+    /// Execute the command of part 1. Returns None, if the submarine overflows.
+    fn execute_command_1(&mut self, command: &Command, param: i64) -> Option<()> {
         match command {
-            Command::Forward => self.position += param,
-            Command::Down => self.depth += param,
-            Command::Up => self.depth -= param,
+            Command::Forward => self.position = self.position.checked_add(param)?,
+            Command::Down => self.depth = self.depth.checked_add(param)?,
+            Command::Up => self.depth = self.depth.checked_sub(param)?,
             _ => {}
         }
+        Some(())
     }
 
-    /// Execute the command of part 2
-    fn execute_command_2(&mut self, command: &Command, param: u32) {
+    /// Execute the command of part 2. Returns None, if the submarine overflows.
+    fn execute_command_2(&mut self, command: &Command, param: i64) -> Option<()> {
         match command {
             Command::Forward => {
-                self.position += param;
-                self.depth += self.aim * param;
+                self.position = self.position.checked_add(param)?;
+                self.depth = self.depth.checked_add(self.aim.checked_mul(param)?)?;
             }
-            Command::Down => self.aim += param,
-            Command::Up => self.aim -= param,
+            Command::Down => self.aim = self.aim.checked_add(param)?,
+            Command::Up => self.aim = self.aim.checked_sub(param)?,
             _ => {}
         }
+        Some(())
+    }
+
+    /// Caluclate the score of the submarine. Returns None, if it overflows.
+    fn score(&self) -> Option<i64> {
+        self.position.checked_mul(self.depth)
     }
+}
+
+/// Parse the command of a line, which must be a direction followed by its parameter.
+/// Empty lines don't contain a command.
+fn parse_command(line: &str, number: usize) -> Result<Option<(Command, i64)>, CommandError> {
+    let mut lexer = Command::lexer(line);
+    let dir = match lexer.next() {
+        None => return Ok(None),
+        Some(dir @ (Command::Forward | Command::Down | Command::Up)) => dir,
+        Some(_) => {
+            return UnknownCommand {
+                line: number,
+                command: line[lexer.span().start..].split(' ').next().unwrap(),
+            }
+            .fail()
+        }
+    };
+
+    // extract the command parameter
+    let param = match lexer.next() {
+        Some(Command::Number(param)) => param,
+        _ => return MissingParameter { line: number }.fail(),
+    };
 
-    /// Caluclate the score of the submarine
-    fn score(&self) -> u32 {
-        self.position * self.depth
+    // nothing may follow the parameter
+    if lexer.next().is_some() {
+        return TrailingInput {
+            line: number,
+            input: &line[lexer.span().start..],
+        }
+        .fail();
     }
+
+    Ok(Some((dir, param)))
 }
 
 /// Executes the exercise of day 02
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
+    // write the header of the trajectory, if it is exported
+    let mut trajectory = match &options.submarine_trajectory {
+        Some(path) => {
+            let mut file = BufWriter::new(File::create(path)?);
+            writeln!(file, "step,model,command,position,depth,aim")?;
+            Some(file)
+        }
+        None => None,
+    };
+
     // Iterate over each line and update submarine position accordingly
     let mut submarines = (Submarine::default(), Submarine::default());
+    let mut step = 0;
+    let mut number = 0;
     while let Some(line) = input.try_next().await? {
-        // parse the command
-        let mut command = Command::lexer(&line);
-
-        // execute command and update submarine state accordingly
-        if let Some(dir) = command.next() {
-            // extract the command parameter
-            let param = if let Some(Command::Number(param)) = command.next() {
-                param
-            } else {
-                continue;
-            };
-
-            // execute command
-            submarines.0.execute_command_1(&dir, param);
-            submarines.1.execute_command_2(&dir, param);
+        number += 1;
+        let (dir, param) = match parse_command(&line, number)? {
+            Some(command) => command,
+            None => continue,
+        };
+
+        // execute command
+        submarines
+            .0
+            .execute_command_1(&dir, param)
+            .context(Overflow { line: number })?;
+        submarines
+            .1
+            .execute_command_2(&dir, param)
+            .context(Overflow { line: number })?;
+
+        step += 1;
+        if let Some(file) = &mut trajectory {
+            for (model, submarine) in [("simple", &submarines.0), ("aimed", &submarines.1)] {
+                writeln!(
+                    file,
+                    "{},{},{},{},{},{}",
+                    step,
+                    model,
+                    line.trim(),
+                    submarine.position,
+                    submarine.depth,
+                    submarine.aim
+                )?;
+            }
+        }
+    }
+    if let Some(mut file) = trajectory {
+        file.flush()?;
+    }
+
+    // the scores are only meaningful as long as the submarine stays below the surface
+    let score = |submarine: &Submarine| match submarine.score() {
+        Some(score) => {
+            u64::try_from(score).map_err(|_| format!("Negative score {} of the submarine", score))
         }
+        None => Err("The score of the submarine doesn't fit into 64 bits".to_owned()),
+    };
+    Ok([score(&submarines.0)?, score(&submarines.1)?])
+}
+
+/// Generate `size` commands with parameters up to `scale`
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let scale = options.scale.unwrap_or(9).max(1);
+
+    // the submarine must never rise above the surface
+    let mut depth = 0;
+    for _ in 0..options.size {
+        let param = rng.gen_range(1..=scale);
+        let command = match rng.gen_range(0..3) {
+            0 => "forward",
+            2 if param <= depth => "up",
+            _ => "down",
+        };
+        match command {
+            "down" => depth += param,
+            "up" => depth -= param,
+            _ => {}
+        }
+        writeln!(output, "{} {}", command, param)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+
+    const EXAMPLE: &str = "\
+forward 5
+down 5
+forward 8
+up 3
+down 8
+forward 2
+";
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines(EXAMPLE), &Options::default()).await.unwrap(),
+            [150, 900]
+        );
+    }
+
+    #[tokio::test]
+    async fn rises_above_surface() {
+        let result = execute(lines("forward 2\nup 3\n"), &Options::default()).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Negative score -6 of the submarine"
+        );
     }
 
-    Ok([submarines.0.score() as u64, submarines.1.score() as u64])
+    #[tokio::test]
+    async fn invalid_commands() {
+        let error = |input| async move {
+            execute(lines(input), &Options::default())
+                .await
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("forward 5\n\nbackward 3\n").await,
+            "Unknown command \"backward\" in line 3"
+        );
+        assert_eq!(
+            error("down\n").await,
+            "Missing or invalid parameter of the command in line 1"
+        );
+        assert_eq!(
+            error("up -1\n").await,
+            "Missing or invalid parameter of the command in line 1"
+        );
+        assert_eq!(
+            error("forward 5\nup 2 down\n").await,
+            "Unexpected input \"down\" after the command in line 2"
+        );
+    }
+
+    #[tokio::test]
+    async fn overflows() {
+        let error = |input| async move {
+            execute(lines(input), &Options::default())
+                .await
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(
+                "forward 9223372036854775807
+down 2
+forward 9223372036854775807
+"
+            )
+            .await,
+            "The submarine leaves the range of 64 bit numbers in line 3"
+        );
+        assert_eq!(
+            error(
+                "down 4611686018427387904
+forward 2
+"
+            )
+            .await,
+            "The submarine leaves the range of 64 bit numbers in line 2"
+        );
+        assert_eq!(
+            error(
+                "forward 4294967296
+down 4294967296
+"
+            )
+            .await,
+            "The score of the submarine doesn't fit into 64 bits"
+        );
+    }
+
+    #[tokio::test]
+    async fn trajectory() {
+        let path = std::env::temp_dir().join(format!(
+            "aoc2021-day02-trajectory-{}.csv",
+            std::process::id()
+        ));
+        let options = Options {
+            submarine_trajectory: Some(path.clone()),
+        };
+        execute(lines("forward 5\ndown 5\nforward 8\n"), &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "\
+step,model,command,position,depth,aim
+1,simple,forward 5,5,0,0
+1,aimed,forward 5,5,0,0
+2,simple,down 5,5,5,0
+2,aimed,down 5,5,0,5
+3,simple,forward 8,13,5,0
+3,aimed,forward 8,13,40,5
+"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
 }
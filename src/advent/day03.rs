@@ -1,5 +1,45 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
-use std::error::Error;
+use num_bigint::BigUint;
+use rand::Rng;
+use snafu::Snafu;
+use std::{
+    collections::HashSet,
+    error::Error,
+    io::{self, Write},
+    ops::Deref,
+};
+
+/// The errors of invalid diagnostic reports
+#[derive(Debug, Snafu)]
+enum DiagnosticError {
+    #[snafu(display("Invalid digit {:?} in line {}", digit, line))]
+    InvalidDigit { line: usize, digit: char },
+
+    #[snafu(display("Empty entry in line {}", line))]
+    EmptyEntry { line: usize },
+
+    #[snafu(display(
+        "Entry in line {} has {} bits, but the first entry has {} bits",
+        line,
+        width,
+        expected
+    ))]
+    InconsistentWidth {
+        line: usize,
+        width: usize,
+        expected: usize,
+    },
+}
+
+/// An entry of the diagnostic report of any width, the most significant bit first
+type Word = Vec<bool>;
+
+/// Helper to get the value of a word
+fn value(word: &[bool]) -> BigUint {
+    word.iter()
+        .fold(BigUint::default(), |value, &bit| (value << 1u8) + bit as u8)
+}
 
 #[derive(Default)]
 struct BinaryDigit {
@@ -8,155 +48,244 @@ struct BinaryDigit {
 }
 
 impl BinaryDigit {
-    fn most_common_digit(&self) -> u32 {
-        if self.one > self.zero {
-            1
-        } else {
-            0
-        }
+    fn most_common_digit(&self) -> bool {
+        self.one > self.zero
     }
 
-    fn least_common_digit(&self) -> u32 {
-        if self.zero > self.one {
-            1
-        } else {
-            0
-        }
+    fn least_common_digit(&self) -> bool {
+        self.zero > self.one
     }
 }
 
+/// The diagnostic report, whose width is taken from its first entry
 #[derive(Default)]
 struct DiagnosticCode {
-    digits: [BinaryDigit; 12],
-    entries: Vec<u64>,
+    digits: Vec<BinaryDigit>,
+    entries: Vec<Word>,
 }
 
 impl DiagnosticCode {
-    fn add_entry(&mut self, entry: &str) {
-        for (char, digit) in entry.chars().zip(self.digits.iter_mut()) {
-            match char {
-                '0' => digit.zero += 1,
-                '1' => digit.one += 1,
-                _ => continue,
+    fn add_entry(&mut self, entry: &str) -> Result<(), DiagnosticError> {
+        let line = self.entries.len() + 1;
+        let word = entry
+            .chars()
+            .map(|digit| match digit {
+                '0' => Ok(false),
+                '1' => Ok(true),
+                _ => InvalidDigit { line, digit }.fail(),
+            })
+            .collect::<Result<Word, _>>()?;
+
+        // the first entry determines the width of all others
+        if word.is_empty() {
+            return EmptyEntry { line }.fail();
+        }
+        if self.entries.is_empty() {
+            self.digits.resize_with(word.len(), Default::default);
+        } else if word.len() != self.digits.len() {
+            return InconsistentWidth {
+                line,
+                width: word.len(),
+                expected: self.digits.len(),
             }
+            .fail();
         }
-        self.entries.push(u64::from_str_radix(entry, 2).unwrap());
+
+        for (&bit, digit) in word.iter().zip(self.digits.iter_mut()) {
+            if bit {
+                digit.one += 1;
+            } else {
+                digit.zero += 1;
+            }
+        }
+        self.entries.push(word);
+
+        Ok(())
     }
 
-    fn gamma_rate(&self) -> u32 {
-        self.digits
+    fn gamma_rate(&self) -> BigUint {
+        let word: Word = self
+            .digits
             .iter()
-            .rev()
-            .enumerate()
-            .map(|(i, d)| d.most_common_digit() * 2u32.pow(i as u32))
-            .sum()
+            .map(BinaryDigit::most_common_digit)
+            .collect();
+        value(&word)
     }
 
-    fn epsilon_rate(&self) -> u32 {
-        self.digits
+    fn epsilon_rate(&self) -> BigUint {
+        let word: Word = self
+            .digits
             .iter()
-            .rev()
-            .enumerate()
-            .map(|(i, d)| d.least_common_digit() * 2u32.pow(i as u32))
-            .sum()
+            .map(BinaryDigit::least_common_digit)
+            .collect();
+        value(&word)
     }
 
-    fn oxygen_generator_rating(&self) -> u64 {
-        if self.entries.is_empty() {
-            return 0;
-        }
+    /// Filter the entries bit by bit until only one is left. At each position, `keep` gets
+    /// the number of ones and zeros of the remaining entries and selects the bit to keep.
+    /// Positions, where all remaining entries lack the selected bit, keep all of them.
+    fn rating(&self, keep: impl Fn(usize, usize) -> bool) -> BigUint {
+        let mut entries: Vec<&Word> = self.entries.iter().collect();
+        for pos in 0..self.digits.len() {
+            if entries.len() <= 1 {
+                break;
+            }
 
-        let mut entries: Vec<_> = self.entries.iter().copied().enumerate().collect();
-        let mut pos = 12;
-        while entries.len() > 1 {
-            pos -= 1;
-
-            let (mut zero, mut one) = (0, 0);
-            let pow = 2u64.pow(pos);
-
-            // get most common digit
-            for (_, entry) in entries.iter() {
-                if *entry >= pow {
-                    one += 1;
-                } else {
-                    zero += 1;
-                }
+            let one = entries.iter().filter(|entry| entry[pos]).count();
+            let bit = keep(one, entries.len() - one);
+            if entries.iter().any(|entry| entry[pos] == bit) {
+                entries.retain(|entry| entry[pos] == bit);
             }
-            let mcd = if one >= zero { 1 } else { 0 };
-
-            // filter entries
-            entries = entries
-                .into_iter()
-                .filter_map(|entry| {
-                    if mcd == 1 && entry.1 >= pow {
-                        Some((entry.0, entry.1 - pow))
-                    } else if mcd == 0 && entry.1 < pow {
-                        Some((entry.0, entry.1))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
         }
 
-        self.entries[dbg!(entries[0].0)]
+        entries
+            .first()
+            .map(|entry| value(entry))
+            .unwrap_or_default()
     }
 
-    fn co2_scrubber_rating(&self) -> u64 {
-        if self.entries.is_empty() {
-            return 0;
-        }
-
-        let mut entries: Vec<_> = self.entries.iter().copied().enumerate().collect();
-        let mut pos = 12;
-        while entries.len() > 1 {
-            pos -= 1;
-
-            let (mut zero, mut one) = (0, 0);
-            let pow = 2u64.pow(pos);
-
-            // get most common digit
-            for (_, entry) in entries.iter() {
-                if *entry >= pow {
-                    one += 1;
-                } else {
-                    zero += 1;
-                }
-            }
-            let mcd = if one < zero { 1 } else { 0 };
-
-            // filter entries
-            entries = entries
-                .into_iter()
-                .filter_map(|entry| {
-                    if mcd == 1 && entry.1 >= pow {
-                        Some((entry.0, entry.1 - pow))
-                    } else if mcd == 0 && entry.1 < pow {
-                        Some((entry.0, entry.1))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-        }
+    fn oxygen_generator_rating(&self) -> BigUint {
+        self.rating(|one, zero| one >= zero)
+    }
 
-        self.entries[dbg!(entries[0].0)]
+    fn co2_scrubber_rating(&self) -> BigUint {
+        self.rating(|one, zero| one < zero)
     }
 }
 
-/// Executes the exercise of day 02
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+/// Helper to check, that a result still fits into the result of the exercise
+fn checked(result: BigUint) -> Result<u64, Box<dyn Error>> {
+    u64::try_from(&result)
+        .map_err(|_| format!("The result {} does not fit into 64 bits", result).into())
+}
+
+/// Executes the exercise of day 03
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
     let mut diagnostics = DiagnosticCode::default();
     while let Some(line) = input.try_next().await? {
-        diagnostics.add_entry(&line);
+        diagnostics.add_entry(&line)?;
     }
 
     Ok([
-        (diagnostics.gamma_rate() * diagnostics.epsilon_rate()) as u64,
-        (diagnostics.oxygen_generator_rating() * diagnostics.co2_scrubber_rating()) as u64,
+        checked(diagnostics.gamma_rate() * diagnostics.epsilon_rate())?,
+        checked(diagnostics.oxygen_generator_rating() * diagnostics.co2_scrubber_rating())?,
     ])
 }
+
+/// Generate `size` distinct diagnostic codes with a bit width of `scale`
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let width = options.scale.unwrap_or(12).max(1);
+
+    // there are only so many distinct codes of the given width
+    let size = if width < usize::BITS {
+        options.size.min(1 << width)
+    } else {
+        options.size
+    };
+
+    let mut codes = HashSet::new();
+    while codes.len() < size {
+        let code: String = (0..width)
+            .map(|_| if rng.gen() { '1' } else { '0' })
+            .collect();
+        if codes.insert(code.clone()) {
+            writeln!(output, "{}", code)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+
+    const EXAMPLE: &str = "\
+00100
+11110
+10110
+10111
+10101
+01111
+00111
+11100
+10000
+11001
+00010
+01010
+";
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(execute(lines(EXAMPLE)).await.unwrap(), [198, 230]);
+    }
+
+    #[test]
+    fn rating_ties() {
+        // both entries tie on the first bit, oxygen keeps the ones, co2 the zeros
+        let mut diagnostics = DiagnosticCode::default();
+        diagnostics.add_entry("100000000000").unwrap();
+        diagnostics.add_entry("011111111111").unwrap();
+
+        assert_eq!(
+            diagnostics.oxygen_generator_rating(),
+            0b100000000000u32.into()
+        );
+        assert_eq!(diagnostics.co2_scrubber_rating(), 0b011111111111u32.into());
+    }
+
+    #[tokio::test]
+    async fn shared_bits() {
+        // the co2 scrubber would drop both 010 and 011 on their shared middle bit
+        let input = "010\n011\n100\n101\n110\n";
+        assert_eq!(execute(lines(input)).await.unwrap(), [6, 0b101 * 0b010]);
+    }
+
+    #[tokio::test]
+    async fn invalid_entries() {
+        let error = |input| async move { execute(lines(input)).await.unwrap_err().to_string() };
+        assert_eq!(
+            error("0101\n0110\n011\n").await,
+            "Entry in line 3 has 3 bits, but the first entry has 4 bits"
+        );
+        assert_eq!(error("0101\n0120\n").await, "Invalid digit '2' in line 2");
+        assert_eq!(error("\n").await, "Empty entry in line 1");
+    }
+
+    #[tokio::test]
+    async fn wide_entries() {
+        // the ratings of 70 bit wide entries are fine, but their product is too large
+        let ones = "1".repeat(70);
+        let zeros = "0".repeat(70);
+        let mut diagnostics = DiagnosticCode::default();
+        diagnostics.add_entry(&ones).unwrap();
+        diagnostics.add_entry(&zeros).unwrap();
+        diagnostics.add_entry(&ones).unwrap();
+
+        let max = (BigUint::from(1u8) << 70u8) - 1u8;
+        assert_eq!(diagnostics.gamma_rate(), max);
+        assert_eq!(diagnostics.epsilon_rate(), 0u8.into());
+        assert_eq!(diagnostics.oxygen_generator_rating(), max);
+        assert_eq!(diagnostics.co2_scrubber_rating(), 0u8.into());
+
+        let (high, low) = (
+            "1".repeat(35) + &"0".repeat(35),
+            "0".repeat(35) + &"1".repeat(35),
+        );
+        let input = format!("{}\n{}\n{}\n", high, high, low);
+        assert!(execute(lines(&input))
+            .await
+            .unwrap_err()
+            .to_string()
+            .ends_with("does not fit into 64 bits"));
+    }
+}
@@ -1,13 +1,78 @@
-use futures::{pin_mut, prelude::*};
+use super::GeneratorOptions;
 use ndarray::prelude::*;
-use std::error::Error;
+use rand::{seq::index, seq::SliceRandom, Rng};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    io::{self, Write},
+};
+use structopt::StructOpt;
+
+// Options of the bingo game. Not a doc comment, as it is flattened into the options of
+// the command line.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    #[structopt(flatten)]
+    pub rules: WinRules,
+
+    /// Print the log of the bingo game of day 04
+    #[structopt(long)]
+    pub bingo_log: bool,
+
+    /// Find the shortest draws, which make this bingo board of day 04 win first, and rank
+    /// all boards by the actual draws
+    #[structopt(long)]
+    pub bingo_target: Option<usize>,
+}
+
+// The rules of the bingo game beyond full rows and columns, which win by default
+#[derive(Debug, Default, Clone, Copy, StructOpt)]
+pub struct WinRules {
+    /// Bingo boards of day 04 also win with a full diagonal, if they are square
+    #[structopt(long)]
+    pub bingo_diagonals: bool,
+
+    /// Bingo boards of day 04 also win with all four corners marked
+    #[structopt(long)]
+    pub bingo_corners: bool,
+
+    /// Bingo boards of day 04 only win with all numbers marked, this overrides all lines
+    #[structopt(long)]
+    pub bingo_full_card: bool,
+}
+
+/// The marked line, with which a board has won
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WinningLine {
+    Row(usize),
+    Column(usize),
+    Diagonal,
+    AntiDiagonal,
+    Corners,
+    FullCard,
+}
+
+impl fmt::Display for WinningLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Row(row) => write!(f, "row {}", row + 1),
+            Self::Column(column) => write!(f, "column {}", column + 1),
+            Self::Diagonal => write!(f, "the diagonal"),
+            Self::AntiDiagonal => write!(f, "the anti-diagonal"),
+            Self::Corners => write!(f, "the four corners"),
+            Self::FullCard => write!(f, "the full card"),
+        }
+    }
+}
 
 /// A board of the bingo game
 #[derive(Default)]
 struct BingoBoard {
     board: Array2<u32>,
     marked: Array2<bool>,
-    won: bool,
+    cells: HashMap<u32, (usize, usize)>,
+    won: Option<WinningLine>,
 }
 
 impl BingoBoard {
@@ -19,56 +84,108 @@ impl BingoBoard {
         let mut board = None;
         for line in lines {
             // read in all numbers of a line
-            let line: Vec<_> = line
+            let line = line
                 .as_ref()
                 .split_whitespace()
-                .map(|e| e.parse().unwrap())
-                .collect();
+                .map(str::parse)
+                .collect::<Result<Vec<_>, _>>()?;
 
             // append line to board
             board
                 .get_or_insert_with(|| Array2::zeros((0, line.len())))
                 .push_row(ArrayView::from(&line))?;
         }
-        let board = board.unwrap();
+        let board = board.ok_or("Empty bingo board")?;
+
+        // index the cells by their number, so draws can be found at once
+        let mut cells = HashMap::new();
+        for (index, &number) in board.indexed_iter() {
+            if cells.insert(number, index).is_some() {
+                return Err(format!("Number {} appears twice on a bingo board", number).into());
+            }
+        }
 
         Ok(Self {
             marked: Array2::from_elem(board.raw_dim(), false),
             board,
-            won: false,
+            cells,
+            won: None,
         })
     }
 
-    /// Apply the given bingo number to the board. Returns true if the board has won
-    fn apply_draw(&mut self, draw: u32) -> bool {
+    /// Apply the given bingo number to the board. Returns the winning line, if the board
+    /// has won
+    fn apply_draw(&mut self, draw: u32, rules: &WinRules) -> Option<WinningLine> {
         // Only continue to apply draws, if the board has not won yet
-        if self.won {
-            return true;
+        if self.won.is_some() {
+            return self.won;
         }
 
-        // find the index of the draw, if present
-        let index = self.board.indexed_iter().fold(None, |res, (index, elem)| {
-            // check if element is equal to draw
-            res.or_else(|| if elem == &draw { Some(index) } else { None })
-        });
-
         // check, if have a match and mark the field
-        if let Some(index) = index {
-            self.marked[index] = true;
+        let (i, j) = *self.cells.get(&draw)?;
+        self.marked[[i, j]] = true;
+
+        // only the lines through the marked field can have been completed
+        let (rows, cols) = self.marked.dim();
+        let full = |line: ArrayView1<bool>| line.iter().all(|&marked| marked);
+        self.won = if rules.bingo_full_card {
+            self.marked
+                .iter()
+                .all(|&marked| marked)
+                .then_some(WinningLine::FullCard)
+        } else if full(self.marked.row(i)) {
+            Some(WinningLine::Row(i))
+        } else if full(self.marked.column(j)) {
+            Some(WinningLine::Column(j))
+        } else if rules.bingo_diagonals && rows == cols && i == j && full(self.marked.diag()) {
+            Some(WinningLine::Diagonal)
+        } else if rules.bingo_diagonals
+            && rows == cols
+            && i + j == cols - 1
+            && (0..rows).all(|k| self.marked[[k, cols - 1 - k]])
+        {
+            Some(WinningLine::AntiDiagonal)
+        } else if rules.bingo_corners
+            && [[0, 0], [0, cols - 1], [rows - 1, 0], [rows - 1, cols - 1]]
+                .iter()
+                .all(|&corner| self.marked[corner])
+        {
+            Some(WinningLine::Corners)
         } else {
-            return false;
+            None
+        };
+
+        self.won
+    }
+
+    /// Get all lines of the board, with which it can win under the given rules
+    fn winning_lines(&self, rules: &WinRules) -> Vec<(WinningLine, Vec<u32>)> {
+        if rules.bingo_full_card {
+            return vec![(WinningLine::FullCard, self.board.iter().copied().collect())];
         }
 
-        // check if board has won
-        self.won = self.marked.rows().into_iter().fold(false, |res, row| {
-            row.into_iter().fold(true, |r, e| r & e) | res
-        });
-        self.won = self.won
-            || self.marked.columns().into_iter().fold(false, |res, col| {
-                col.into_iter().fold(true, |r, e| r & e) | res
-            });
+        let (rows, cols) = self.board.dim();
+        let mut lines = Vec::new();
+        for (i, row) in self.board.rows().into_iter().enumerate() {
+            lines.push((WinningLine::Row(i), row.to_vec()));
+        }
+        for (j, column) in self.board.columns().into_iter().enumerate() {
+            lines.push((WinningLine::Column(j), column.to_vec()));
+        }
+        if rules.bingo_diagonals && rows == cols {
+            lines.push((WinningLine::Diagonal, self.board.diag().to_vec()));
+            let anti = (0..rows).map(|k| self.board[[k, cols - 1 - k]]).collect();
+            lines.push((WinningLine::AntiDiagonal, anti));
+        }
+        if rules.bingo_corners {
+            let corners = [[0, 0], [0, cols - 1], [rows - 1, 0], [rows - 1, cols - 1]];
+            lines.push((
+                WinningLine::Corners,
+                corners.iter().map(|&corner| self.board[corner]).collect(),
+            ));
+        }
 
-        self.won
+        lines
     }
 
     /// Calculate the score of the board
@@ -82,56 +199,361 @@ impl BingoBoard {
     }
 }
 
-/// Executes the exercise of day 4
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
-) -> Result<[u64; 2], Box<dyn Error>> {
-    pin_mut!(input);
+/// An entry of the game log, when a board has won
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Win {
+    /// Index of the board in the input
+    board: usize,
+    /// Index of the winning draw
+    turn: usize,
+    draw: u32,
+    line: WinningLine,
+    score: u32,
+}
 
-    // fuse the input stream to see if new lines are still readable
-    let mut input = input.fuse();
+/// Apply the draws to the boards one after the other and log each board, once it has won
+fn play(boards: &mut [BingoBoard], draws: &[u32], rules: &WinRules) -> Vec<Win> {
+    let mut log = Vec::new();
+    for (turn, &draw) in draws.iter().enumerate() {
+        for (index, board) in boards.iter_mut().enumerate() {
+            // boards, which have already won, are not logged again
+            if board.won.is_some() {
+                continue;
+            }
+
+            // apply draw and if the board has won, calculate final score
+            if let Some(line) = board.apply_draw(draw, rules) {
+                log.push(Win {
+                    board: index,
+                    turn,
+                    draw,
+                    line,
+                    score: board.score() * draw,
+                });
+            }
+        }
+    }
+
+    log
+}
+
+/// Find the shortest draws, which make the target board win before all others. Any
+/// winning draws contain a line of the target, but must not contain a line of another
+/// board, so the shortest of these lines are the answer.
+fn shortest_win(
+    boards: &[BingoBoard],
+    target: usize,
+    rules: &WinRules,
+) -> Option<(WinningLine, Vec<u32>)> {
+    let others: Vec<_> = boards
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| index != target)
+        .flat_map(|(_, board)| board.winning_lines(rules))
+        .collect();
+
+    boards[target]
+        .winning_lines(rules)
+        .into_iter()
+        .filter(|(_, draws)| {
+            let draws: HashSet<_> = draws.iter().collect();
+            !others
+                .iter()
+                .any(|(_, line)| line.iter().all(|number| draws.contains(number)))
+        })
+        .min_by_key(|(_, draws)| draws.len())
+}
+
+/// Get the rank and the winning turn of each board from the game log
+fn ranking(boards: usize, log: &[Win]) -> Vec<Option<(usize, usize)>> {
+    let mut ranks = vec![None; boards];
+    for (rank, win) in log.iter().enumerate() {
+        ranks[win.board] = Some((rank, win.turn));
+    }
+    ranks
+}
+
+/// Executes the exercise of day 4
+pub fn execute(input: &str, options: &Options) -> Result<[u64; 2], Box<dyn Error>> {
+    let mut lines = input.lines().peekable();
 
     // read the first line to extract the drawn bingo numbers
-    let draws: Vec<u32> = input
-        .try_next()
-        .await?
-        .unwrap()
+    let draws = lines
+        .next()
+        .ok_or("Missing bingo draws")?
         .split(',')
-        .map(|draw| draw.parse().unwrap())
-        .collect();
+        .map(str::parse)
+        .collect::<Result<Vec<u32>, _>>()?;
 
     // read in bingo boards as long as new lines are available
     let mut boards = Vec::new();
-    while !input.is_done() {
-        // read all line until an empty line and generate a board
-        let lines: Vec<_> = input
-            .by_ref()
-            .try_take_while(|elem| future::ok(!elem.is_empty()))
-            .try_collect()
-            .await?;
-        if !lines.is_empty() {
-            boards.push(BingoBoard::from_lines(lines)?);
-        }
-    }
-
-    // apply the drawn numbers to each board and find
-    let mut first = None;
-    let mut last = None;
-    for draw in draws {
-        for board in &mut boards {
-            // apply draw and if the board has won, calculate final score
-            if board.apply_draw(draw) {
-                let score = board.score() * draw;
-                if first.is_none() {
-                    first = Some(score);
-                }
-                last = Some(score);
+    while lines.peek().is_some() {
+        // take all lines until an empty line and generate a board
+        let board: Vec<_> = lines.by_ref().take_while(|line| !line.is_empty()).collect();
+        if !board.is_empty() {
+            boards.push(BingoBoard::from_lines(board)?);
+        }
+    }
+
+    // play the game and look up the first and the last winner
+    let log = play(&mut boards, &draws, &options.rules);
+    if options.bingo_log {
+        for win in &log {
+            println!(
+                "Board {} won with draw {} ({}) on {} and a score of {}",
+                win.board + 1,
+                win.turn + 1,
+                win.draw,
+                win.line,
+                win.score
+            );
+        }
+    }
+
+    // analyse the chosen board and rank all of them
+    if let Some(target) = options.bingo_target {
+        if !(1..=boards.len()).contains(&target) {
+            return Err(format!("There is no bingo board {}", target).into());
+        }
+        match shortest_win(&boards, target - 1, &options.rules) {
+            Some((line, draws)) => {
+                let draws: Vec<_> = draws.iter().map(u32::to_string).collect();
+                println!(
+                    "Board {} wins first with {} by drawing {}",
+                    target,
+                    line,
+                    draws.join(",")
+                );
             }
+            None => println!("Board {} can never win first", target),
         }
 
-        // Throw away boards that have already won
-        boards.retain(|b| !b.won);
+        for (board, rank) in ranking(boards.len(), &log).into_iter().enumerate() {
+            match rank {
+                Some((rank, turn)) => println!(
+                    "Board {} ranks {} and wins with draw {}",
+                    board + 1,
+                    rank + 1,
+                    turn + 1
+                ),
+                None => println!("Board {} never wins", board + 1),
+            }
+        }
     }
 
-    Ok([first.unwrap() as u64, last.unwrap() as u64])
+    let first = log.first().ok_or("No bingo board has won")?;
+    let last = log.last().ok_or("No bingo board has won")?;
+    Ok([first.score as u64, last.score as u64])
+}
+
+/// Generate `size` bingo boards with numbers below `scale` and draw every number once,
+/// so each board wins eventually
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    const SIZE: usize = 5;
+    let scale = (options.scale.unwrap_or(100) as usize).max(SIZE * SIZE);
+    let width = (scale - 1).to_string().len();
+
+    // draw all numbers in random order
+    let mut draws: Vec<_> = (0..scale).collect();
+    draws.shuffle(rng);
+    let draws: Vec<_> = draws.iter().map(|draw| draw.to_string()).collect();
+    writeln!(output, "{}", draws.join(","))?;
+
+    // fill each board with distinct numbers
+    for _ in 0..options.size {
+        writeln!(output)?;
+        let numbers = index::sample(rng, scale, SIZE * SIZE).into_vec();
+        for row in numbers.chunks(SIZE) {
+            let row: Vec<_> = row
+                .iter()
+                .map(|number| format!("{:>width$}", number, width = width))
+                .collect();
+            writeln!(output, "{}", row.join(" "))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+22 13 17 11  0
+ 8  2 23  4 24
+21  9 14 16  7
+ 6 10  3 18  5
+ 1 12 20 15 19
+
+ 3 15  0  2 22
+ 9 18 13 17  5
+19  8  7 25 23
+20 11 10 24  4
+14 21 16 12  6
+
+14 21 17 24  4
+10 16 15  9 19
+18  8 23 26 20
+22 11 13  6  5
+ 2  0 12  3  7
+";
+
+    #[test]
+    fn example() {
+        assert_eq!(execute(EXAMPLE, &Options::default()).unwrap(), [4512, 1924]);
+    }
+
+    #[test]
+    fn column_win() {
+        let mut board = BingoBoard::from_lines(["1 2", "3 4"]).unwrap();
+        let rules = WinRules::default();
+
+        assert_eq!(board.apply_draw(2, &rules), None);
+        assert_eq!(board.apply_draw(5, &rules), None);
+        assert_eq!(board.apply_draw(4, &rules), Some(WinningLine::Column(1)));
+        assert_eq!(board.score(), 4);
+    }
+
+    #[test]
+    fn win_rules() {
+        let board = || BingoBoard::from_lines(["1 2 3", "4 5 6", "7 8 9"]).unwrap();
+        let apply = |rules: WinRules, draws: &[u32]| {
+            let mut board = board();
+            draws
+                .iter()
+                .find_map(|&draw| board.apply_draw(draw, &rules))
+        };
+        let rules = |bingo_diagonals, bingo_corners, bingo_full_card| WinRules {
+            bingo_diagonals,
+            bingo_corners,
+            bingo_full_card,
+        };
+
+        assert_eq!(apply(rules(false, false, false), &[1, 5, 9]), None);
+        assert_eq!(
+            apply(rules(true, false, false), &[1, 5, 9]),
+            Some(WinningLine::Diagonal)
+        );
+        assert_eq!(
+            apply(rules(true, false, false), &[3, 5, 7]),
+            Some(WinningLine::AntiDiagonal)
+        );
+        assert_eq!(
+            apply(rules(false, true, false), &[1, 3, 7, 9]),
+            Some(WinningLine::Corners)
+        );
+
+        assert_eq!(
+            apply(rules(true, true, true), &[1, 2, 3, 5, 9, 7, 4, 6]),
+            None
+        );
+        assert_eq!(
+            apply(rules(false, false, true), &[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+            Some(WinningLine::FullCard)
+        );
+    }
+
+    #[test]
+    fn non_square_board() {
+        let mut boards = vec![
+            BingoBoard::from_lines(["1 2 3", "4 5 6"]).unwrap(),
+            BingoBoard::from_lines(["3 6", "1 4", "2 5"]).unwrap(),
+        ];
+        let rules = WinRules {
+            bingo_diagonals: true,
+            ..Default::default()
+        };
+
+        // the diagonal 1, 5 of the first board doesn't count, as it isn't square
+        let log = play(&mut boards, &[1, 5, 3, 6, 2], &rules);
+        assert_eq!(
+            log,
+            [
+                Win {
+                    board: 0,
+                    turn: 3,
+                    draw: 6,
+                    line: WinningLine::Column(2),
+                    score: 6 * 6,
+                },
+                Win {
+                    board: 1,
+                    turn: 3,
+                    draw: 6,
+                    line: WinningLine::Row(0),
+                    score: 6 * 6,
+                },
+            ]
+        );
+    }
+
+    /// Helper to read the boards of the example
+    fn example_boards() -> Vec<BingoBoard> {
+        EXAMPLE
+            .split("\n\n")
+            .skip(1)
+            .map(|board| BingoBoard::from_lines(board.lines()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn shortest_win_is_first() {
+        let rules = WinRules::default();
+        for target in 0..3 {
+            let (line, draws) = shortest_win(&example_boards(), target, &rules).unwrap();
+            assert_eq!(draws.len(), 5);
+
+            let log = play(&mut example_boards(), &draws, &rules);
+            assert_eq!((log[0].board, log[0].line), (target, line));
+        }
+    }
+
+    #[test]
+    fn shortest_win_with_shared_lines() {
+        // the first row of the second board is the first column of the first board
+        let boards = [
+            BingoBoard::from_lines(["1 2", "3 4"]).unwrap(),
+            BingoBoard::from_lines(["1 3", "5 6"]).unwrap(),
+        ];
+        let rules = WinRules::default();
+        assert_eq!(
+            shortest_win(&boards, 1, &rules),
+            Some((WinningLine::Row(1), vec![5, 6]))
+        );
+
+        let rules = WinRules {
+            bingo_corners: true,
+            ..Default::default()
+        };
+        let boards = [
+            BingoBoard::from_lines(["1 2", "3 4"]).unwrap(),
+            BingoBoard::from_lines(["4 3", "2 1"]).unwrap(),
+        ];
+        assert_eq!(shortest_win(&boards, 0, &rules), None);
+    }
+
+    #[test]
+    fn ranks_of_example() {
+        let draws = [
+            7, 4, 9, 5, 11, 17, 23, 2, 0, 14, 21, 24, 10, 16, 13, 6, 15, 25, 12, 22, 18, 20, 8, 19,
+            3, 26, 1,
+        ];
+        let log = play(&mut example_boards(), &draws, &WinRules::default());
+        assert_eq!(
+            ranking(4, &log),
+            [Some((1, 13)), Some((2, 14)), Some((0, 11)), None]
+        );
+    }
+
+    #[test]
+    fn duplicate_numbers() {
+        assert!(BingoBoard::from_lines(["1 2", "2 3"]).is_err());
+    }
 }
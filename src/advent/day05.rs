@@ -1,13 +1,44 @@
-use cgmath::{prelude::*, Vector2};
+use super::GeneratorOptions;
+use cgmath::{Array, Vector2};
 use futures::{pin_mut, prelude::*};
 use logos::{Lexer, Logos};
 use ndarray::prelude::*;
-use std::error::Error;
+use rand::Rng;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Deref,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+// Options of the hydrothermal vents. Not a doc comment, as it is flattened into the
+// options of the command line.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    /// Count the overlapping vents of day 05 from the line segments, instead of rendering
+    /// them. This is much faster for huge coordinates, but only supports straight and
+    /// diagonal lines.
+    #[structopt(long)]
+    pub vents_analytic: bool,
+
+    /// Save the overlaps of the straight and of all vents of day 05 as PGM images into
+    /// this directory
+    #[structopt(long, parse(from_os_str))]
+    pub vents_heatmap: Option<PathBuf>,
+
+    /// List this many points of day 05 with the most vents
+    #[structopt(long)]
+    pub vents_top: Option<usize>,
+}
 
 /// The token of the line segment input
 #[derive(Logos, Debug, PartialEq)]
 enum LineSegment {
-    #[regex("[0-9]+,[0-9]+", parse_point)]
+    #[regex("-?[0-9]+,-?[0-9]+", parse_point)]
     Point(Vector2<i32>),
 
     #[regex("->", logos::skip)]
@@ -41,18 +72,25 @@ fn get_point(segment: &mut Lexer<LineSegment>) -> Option<Vector2<i32>> {
     })
 }
 
-/// Helper to render a line segment using Bresenham-Algorithm
-fn render_line(mut grid: ArrayViewMut2<u32>, start: Vector2<i32>, end: Vector2<i32>) {
-    // get sloap and step width
-    let delta = Vector2::new((end.x - start.x).abs(), -(end.y - start.y).abs());
-    let step = Vector2::new((end.x - start.x).signum(), (end.y - start.y).signum());
+/// Helper to render a line segment using Bresenham-Algorithm, which marks each point of
+/// the line
+fn render_line(start: Vector2<i32>, end: Vector2<i32>, mut mark: impl FnMut(Vector2<i32>)) {
+    // get sloap and step width, in 64 bits as lines may span all 32 bit coordinates
+    let delta = Vector2::new(
+        (end.x as i64 - start.x as i64).abs(),
+        -(end.y as i64 - start.y as i64).abs(),
+    );
+    let step = Vector2::new(
+        (end.x as i64 - start.x as i64).signum() as i32,
+        (end.y as i64 - start.y as i64).signum() as i32,
+    );
 
     // render line
     let mut pos = start;
     let mut err = delta.sum();
     let mut e2;
     loop {
-        grid[[pos.x as usize, pos.y as usize]] += 1;
+        mark(pos);
         if pos == end {
             break;
         }
@@ -68,37 +106,625 @@ fn render_line(mut grid: ArrayViewMut2<u32>, start: Vector2<i32>, end: Vector2<i
     }
 }
 
+/// Helper to get the number of points of a horizontal, vertical or diagonal line segment
+fn length(start: Vector2<i32>, end: Vector2<i32>) -> i64 {
+    let delta = (end.x as i64 - start.x as i64)
+        .abs()
+        .max((end.y as i64 - start.y as i64).abs());
+    delta + 1
+}
+
+/// The number of vents at each point covered by the line segments
+enum Grid {
+    /// Grid over the bounding box of the line segments, moved to their lowest corner
+    Dense {
+        origin: Vector2<i32>,
+        counts: Array2<u32>,
+    },
+
+    /// Grid of only the covered points, if the bounding box is way larger than the lines
+    Sparse(HashMap<Vector2<i32>, u32>),
+}
+
+impl Grid {
+    /// Dense grids may be this many times larger than the total length of the lines
+    const MAX_DENSITY: i64 = 16;
+
+    /// The largest dense grid, which takes 256 MiB
+    const MAX_CELLS: i64 = 1 << 26;
+
+    /// The most points of the lines, which are rendered into a sparse grid
+    const MAX_POINTS: i64 = 1 << 24;
+
+    /// Create an empty grid, which fits the given line segments. Fails, if the lines
+    /// cover too many points to render them into either grid.
+    fn new(segments: &[(Vector2<i32>, Vector2<i32>)]) -> Result<Self, String> {
+        let points = segments.iter().flat_map(|&(start, end)| [start, end]);
+        let min = points
+            .clone()
+            .reduce(|a, b| Vector2::new(a.x.min(b.x), a.y.min(b.y)));
+        let max = points.reduce(|a, b| Vector2::new(a.x.max(b.x), a.y.max(b.y)));
+        let (min, max) = match min.zip(max) {
+            Some(bounds) => bounds,
+            None => return Ok(Self::Sparse(HashMap::new())),
+        };
+
+        // only use a dense grid, if the lines fill a fair share of it
+        let total: i64 = segments
+            .iter()
+            .map(|&(start, end)| length(start, end))
+            .sum();
+        let shape = (
+            max.x as i64 - min.x as i64 + 1,
+            max.y as i64 - min.y as i64 + 1,
+        );
+        // grids spanning most of the coordinates overflow, but are sparse anyway
+        let area = shape.0.checked_mul(shape.1);
+        let budget = total.checked_mul(Self::MAX_DENSITY);
+        let fits = area.is_some_and(|area| area <= Self::MAX_CELLS);
+        let filled = area
+            .zip(budget)
+            .is_some_and(|(area, budget)| area <= budget);
+        if fits && (filled || total > Self::MAX_POINTS) {
+            Ok(Self::Dense {
+                origin: min,
+                counts: Array2::zeros((shape.0 as usize, shape.1 as usize)),
+            })
+        } else if total <= Self::MAX_POINTS {
+            Ok(Self::Sparse(HashMap::new()))
+        } else {
+            Err(format!(
+                "The vents cover {} points, which are too many to render them, try --vents-analytic",
+                total
+            ))
+        }
+    }
+
+    /// Add a vent at the given point, which must be within the bounds of the grid
+    fn mark(&mut self, pos: Vector2<i32>) {
+        match self {
+            Self::Dense { origin, counts } => {
+                counts[[(pos.x - origin.x) as usize, (pos.y - origin.y) as usize]] += 1
+            }
+            Self::Sparse(counts) => *counts.entry(pos).or_default() += 1,
+        }
+    }
+
+    /// Count the points, where at least two lines overlap
+    fn overlaps(&self) -> u64 {
+        let overlaps = match self {
+            Self::Dense { counts, .. } => counts.iter().filter(|&&count| count >= 2).count(),
+            Self::Sparse(counts) => counts.values().filter(|&&count| count >= 2).count(),
+        };
+        overlaps as u64
+    }
+
+    /// Get the number of vents at each covered point
+    fn points(&self) -> Box<dyn Iterator<Item = (Vector2<i32>, u32)> + '_> {
+        match self {
+            Self::Dense { origin, counts } => {
+                Box::new(counts.indexed_iter().filter(|(_, &count)| count > 0).map(
+                    move |((x, y), &count)| {
+                        (
+                            Vector2::new(origin.x + x as i32, origin.y + y as i32),
+                            count,
+                        )
+                    },
+                ))
+            }
+            Self::Sparse(counts) => Box::new(counts.iter().map(|(&pos, &count)| (pos, count))),
+        }
+    }
+
+    /// Get the `n` points with the most vents, ties are ordered by their coordinates
+    fn hottest(&self, n: usize) -> Vec<(Vector2<i32>, u32)> {
+        let mut points: Vec<_> = self.points().collect();
+        points.sort_unstable_by_key(|&(pos, count)| (Reverse(count), pos.x, pos.y));
+        points.truncate(n);
+        points
+    }
+
+    /// Write the grid as grayscale PGM image, whose brightness is scaled by the number
+    /// of vents. Only dense grids can be exported, sparse ones are way too large.
+    fn write_heatmap(&self, output: &mut impl Write) -> io::Result<()> {
+        let counts = match self {
+            Self::Dense { counts, .. } => counts,
+            Self::Sparse(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The vents are spread too far to export them as image",
+                ))
+            }
+        };
+
+        // the image is indexed by x first, so its rows are the columns of the grid
+        let max = counts.iter().copied().max().unwrap_or(0).max(1);
+        writeln!(output, "P5\n{} {}\n255", counts.nrows(), counts.ncols())?;
+        let pixels: Vec<u8> = counts
+            .t()
+            .iter()
+            .map(|&count| (count as u64 * 255 / max as u64) as u8)
+            .collect();
+        output.write_all(&pixels)
+    }
+}
+
+/// Render all line segments into a grid, which fits them
+fn render(segments: &[(Vector2<i32>, Vector2<i32>)]) -> Result<Grid, String> {
+    // add a one to each field occupied by a vent
+    let mut grid = Grid::new(segments)?;
+    for &(start, end) in segments {
+        render_line(start, end, |pos| grid.mark(pos));
+    }
+    Ok(grid)
+}
+
+/// A horizontal, vertical or diagonal line segment, which goes from `start` `length`
+/// steps into the direction of `step`
+#[derive(Debug, Clone, Copy)]
+struct Ray {
+    start: Vector2<i64>,
+    step: Vector2<i64>,
+    length: i64,
+}
+
+impl Ray {
+    /// The directions of the rays, their start always has the lowest x, or lowest y
+    /// for vertical rays
+    const STEPS: [(i64, i64); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+    /// Create the ray of a line segment, if it is horizontal, vertical or diagonal
+    fn new(start: Vector2<i32>, end: Vector2<i32>) -> Option<Self> {
+        let (mut start, mut end) = (start.cast::<i64>()?, end.cast::<i64>()?);
+        if (end.x, end.y) < (start.x, start.y) {
+            std::mem::swap(&mut start, &mut end);
+        }
+
+        let delta = end - start;
+        if delta.x != 0 && delta.y != 0 && delta.x.abs() != delta.y.abs() {
+            return None;
+        }
+        // single points are taken as horizontal rays
+        let step = match (delta.x.signum(), delta.y.signum()) {
+            (0, 0) => Vector2::new(1, 0),
+            (x, y) => Vector2::new(x, y),
+        };
+        Some(Self {
+            start,
+            step,
+            length: delta.x.abs().max(delta.y.abs()),
+        })
+    }
+
+    /// Identify the infinite line through a point into the given direction
+    fn line(point: Vector2<i64>, step: Vector2<i64>) -> (Vector2<i64>, i64) {
+        (step, point.x * step.y - point.y * step.x)
+    }
+
+    /// Get the point of a line at the given coordinate along it
+    fn at((step, offset): (Vector2<i64>, i64), position: i64) -> Vector2<i64> {
+        if step.x != 0 {
+            Vector2::new(position, position * step.y - offset)
+        } else {
+            Vector2::new(offset, position)
+        }
+    }
+
+    /// Get the coordinate of a point along a line into the given direction
+    fn position(point: Vector2<i64>, step: Vector2<i64>) -> i64 {
+        if step.x != 0 {
+            point.x
+        } else {
+            point.y
+        }
+    }
+
+    /// Find the lattice point, where two rays of different directions cross
+    fn crossing(&self, other: &Self) -> Option<Vector2<i64>> {
+        let cross = |a: Vector2<i64>, b: Vector2<i64>| a.x * b.y - a.y * b.x;
+        let det = cross(self.step, other.step);
+        if det == 0 {
+            return None;
+        }
+
+        // solve start + t * step = other.start + u * other.step
+        let delta = other.start - self.start;
+        let (t, u) = (cross(delta, other.step), cross(delta, self.step));
+        if t % det != 0 || u % det != 0 {
+            return None;
+        }
+        let (t, u) = (t / det, u / det);
+        if (0..=self.length).contains(&t) && (0..=other.length).contains(&u) {
+            Some(self.start + self.step * t)
+        } else {
+            None
+        }
+    }
+}
+
+/// Count the points, where at least two line segments overlap, without rendering them.
+/// Collinear segments overlap in intervals along their line, all others cross in at most
+/// one point, which only counts, if it is not within such an interval already.
+fn count_analytic(segments: &[(Vector2<i32>, Vector2<i32>)]) -> Result<u64, Box<dyn Error>> {
+    let rays = segments
+        .iter()
+        .map(|&(start, end)| {
+            Ray::new(start, end).ok_or_else(|| {
+                format!(
+                    "Line segment {},{} -> {},{} is neither straight nor diagonal",
+                    start.x, start.y, end.x, end.y
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // sweep along each line to find the intervals covered by two or more segments
+    let mut events: HashMap<_, Vec<_>> = HashMap::new();
+    for ray in &rays {
+        let position = Ray::position(ray.start, ray.step);
+        let events = events.entry(Ray::line(ray.start, ray.step)).or_default();
+        events.push((position, 1));
+        events.push((position + ray.length + 1, -1));
+    }
+    let mut overlaps: HashMap<_, Vec<(i64, i64)>> = HashMap::new();
+    let mut intervals = Vec::new();
+    for (line, mut events) in events {
+        events.sort_unstable();
+        let mut covered = 0;
+        for (i, &(position, change)) in events.iter().enumerate() {
+            covered += change;
+            let next = events.get(i + 1).map(|&(next, _)| next);
+            match next {
+                Some(next) if covered >= 2 && next > position => {
+                    overlaps.entry(line).or_default().push((position, next - 1));
+                    intervals.push(Ray {
+                        start: Ray::at(line, position),
+                        step: line.0,
+                        length: next - 1 - position,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // helper to count the lines through a point, which overlap at the point
+    let overlapped = |point: Vector2<i64>| {
+        Ray::STEPS
+            .iter()
+            .filter(|&&(x, y)| {
+                let step = Vector2::new(x, y);
+                let position = Ray::position(point, step);
+                overlaps
+                    .get(&Ray::line(point, step))
+                    .is_some_and(|intervals| {
+                        let i = intervals.partition_point(|&(_, end)| end < position);
+                        intervals
+                            .get(i)
+                            .is_some_and(|&(start, _)| start <= position)
+                    })
+            })
+            .count() as u64
+    };
+
+    // count the points of the intervals, but those on several lines only once
+    let mut count: u64 = intervals.iter().map(|ray| ray.length as u64 + 1).sum();
+    let mut shared = HashSet::new();
+    for (i, a) in intervals.iter().enumerate() {
+        for b in &intervals[i + 1..] {
+            shared.extend(a.crossing(b));
+        }
+    }
+    count -= shared
+        .into_iter()
+        .map(|point| overlapped(point) - 1)
+        .sum::<u64>();
+
+    // add all crossings of two segments, which are not overlapped already
+    let mut crossings = HashSet::new();
+    for (i, a) in rays.iter().enumerate() {
+        for b in &rays[i + 1..] {
+            if let Some(point) = a.crossing(b) {
+                if overlapped(point) == 0 {
+                    crossings.insert(point);
+                }
+            }
+        }
+    }
+
+    Ok(count + crossings.len() as u64)
+}
+
 /// Executes the exercise of day 5
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
-    // read in all line segments and add a one to each field
-    // occupied by a vent
-    let mut grids = Array3::zeros((2, 1000, 1000));
+    // read in all line segments to find the bounds of the grids
+    let mut segments = Vec::new();
     while let Some(line) = input.try_next().await? {
         // parse the line segment
         let mut segment = LineSegment::lexer(&line);
 
         // extract start and end of line
-        let start = get_point(&mut segment).unwrap();
-        let end = get_point(&mut segment).unwrap();
+        let start = get_point(&mut segment);
+        let end = get_point(&mut segment);
+        match start.zip(end) {
+            Some(segment) => segments.push(segment),
+            None => return Err(format!("Invalid line segment {:?}", &*line).into()),
+        }
+    }
 
-        // render all lines in grid 2
-        render_line(grids.slice_mut(s![1, .., ..]), start, end);
+    // part 1 only considers horizontal/vertical lines, part 2 all lines
+    let straight: Vec<_> = segments
+        .iter()
+        .copied()
+        .filter(|(start, end)| start.x == end.x || start.y == end.y)
+        .collect();
+    if options.vents_analytic {
+        if options.vents_heatmap.is_some() || options.vents_top.is_some() {
+            return Err("The heatmap needs the rendered grids, not the analytic count".into());
+        }
+        return Ok([count_analytic(&straight)?, count_analytic(&segments)?]);
+    }
+
+    let mut overlap = [0; 2];
+    for (i, (name, segments)) in [("straight", &straight), ("all", &segments)]
+        .into_iter()
+        .enumerate()
+    {
+        let grid = render(segments)?;
+        overlap[i] = grid.overlaps();
 
-        // render only horizontal/vertical lines in grid 1
-        if start.x == end.x || start.y == end.y {
-            render_line(grids.slice_mut(s![0, .., ..]), start, end);
+        // export the heatmap and list the hottest points
+        if let Some(dir) = &options.vents_heatmap {
+            let path = dir.join(format!("vents-{}.pgm", name));
+            let mut file = BufWriter::new(File::create(&path)?);
+            grid.write_heatmap(&mut file)?;
+            file.flush()?;
+            println!("Saved heatmap of {} lines to {}", name, path.display());
+        }
+        if let Some(n) = options.vents_top {
+            println!("Hottest points of {} lines:", name);
+            for (pos, count) in grid.hottest(n) {
+                println!("  {},{}: {} vents", pos.x, pos.y, count);
+            }
         }
     }
 
-    // count fields with two overlapping lines
-    let overlap: Vec<_> = grids
-        .axis_iter(Axis(0))
-        .map(|grid| grid.iter().filter(|&e| *e >= 2).map(|_| 1).sum())
-        .collect();
+    Ok(overlap)
+}
+
+/// Generate `size` horizontal, vertical or diagonal line segments with coordinates
+/// below `scale`
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let scale = options.scale.unwrap_or(1000).max(1) as i32;
+
+    for _ in 0..options.size {
+        let start = Vector2::new(rng.gen_range(0..scale), rng.gen_range(0..scale));
+        let end = match rng.gen_range(0..3) {
+            0 => Vector2::new(rng.gen_range(0..scale), start.y),
+            1 => Vector2::new(start.x, rng.gen_range(0..scale)),
+            _ => {
+                // walk diagonally in a random direction without leaving the map
+                let step = Vector2::new(
+                    if rng.gen() { 1 } else { -1 },
+                    if rng.gen() { 1 } else { -1 },
+                );
+                let room = |pos: i32, step: i32| if step > 0 { scale - 1 - pos } else { pos };
+                let length = rng.gen_range(0..=room(start.x, step.x).min(room(start.y, step.y)));
+                start + step * length
+            }
+        };
+        writeln!(output, "{},{} -> {},{}", start.x, start.y, end.x, end.y)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Helper to read the line segments of an input
+    fn segments(input: &str) -> Vec<(Vector2<i32>, Vector2<i32>)> {
+        input
+            .lines()
+            .map(|line| {
+                let mut segment = LineSegment::lexer(line);
+                (
+                    get_point(&mut segment).unwrap(),
+                    get_point(&mut segment).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    const EXAMPLE: &str = "\
+0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2
+";
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines(EXAMPLE), &Options::default()).await.unwrap(),
+            [5, 12]
+        );
+    }
+
+    #[test]
+    fn diagonals_in_all_directions() {
+        let lines = [
+            ((0, 0), (3, 3)),
+            ((3, 3), (0, 0)),
+            ((0, 3), (3, 0)),
+            ((3, 0), (0, 3)),
+        ];
+        for (start, end) in lines {
+            let mut grid = Array2::<u32>::zeros((4, 4));
+            render_line(
+                Vector2::new(start.0, start.1),
+                Vector2::new(end.0, end.1),
+                |pos| grid[[pos.x as usize, pos.y as usize]] += 1,
+            );
+
+            // all four cells of the diagonal are marked exactly once
+            assert_eq!(grid.sum(), 4);
+            for i in 0..4 {
+                let y = if start.0 == start.1 { i } else { 3 - i };
+                assert_eq!(grid[[i, y]], 1);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn diagonals_crossing() {
+        let input = "0,0 -> 2,2\n4,4 -> 2,2\n4,0 -> 2,2\n0,4 -> 2,2\n";
+        assert_eq!(
+            execute(lines(input), &Options::default()).await.unwrap(),
+            [0, 1]
+        );
+    }
+
+    #[tokio::test]
+    async fn large_and_negative_coordinates() {
+        let input = "\
+-5,-5 -> 5,5
+5,-5 -> -5,5
+1000000,3 -> 1000000,-3
+999998,0 -> 1000002,0
+";
+        assert_eq!(
+            execute(lines(input), &Options::default()).await.unwrap(),
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn sparse_grid_for_distant_lines() {
+        let near = (Vector2::new(0, 0), Vector2::new(3, 0));
+        let far = (Vector2::new(0, 2000000), Vector2::new(0, 2000003));
+        assert!(matches!(Grid::new(&[near]), Ok(Grid::Dense { .. })));
+        assert!(matches!(Grid::new(&[near, far]), Ok(Grid::Sparse(_))));
+        assert!(matches!(Grid::new(&[]), Ok(Grid::Sparse(_))));
+    }
+
+    #[tokio::test]
+    async fn invalid_segment() {
+        let error = execute(lines("1,2 -> 3\n"), &Options::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid line segment \"1,2 -> 3\"");
+    }
+
+    #[tokio::test]
+    async fn analytic_example() {
+        let options = Options {
+            vents_analytic: true,
+            ..Default::default()
+        };
+        assert_eq!(execute(lines(EXAMPLE), &options).await.unwrap(), [5, 12]);
+    }
 
-    Ok([overlap[0], overlap[1]])
+    #[test]
+    fn analytic_huge_coordinates() {
+        let input = "\
+0,0 -> 3000000,0
+1000000,0 -> 4000000,0
+500000,-5 -> 500000,5
+0,-1000000 -> 2000000,1000000
+";
+        // the collinear overlap plus the crossings, which are not part of it
+        assert_eq!(count_analytic(&segments(input)).unwrap(), 2000001 + 1);
+    }
+
+    #[test]
+    fn analytic_rejects_other_slopes() {
+        assert!(count_analytic(&segments("0,0 -> 2,1")).is_err());
+    }
+
+    #[test]
+    fn heatmap() {
+        let grid = render(&segments("1,1 -> 3,1\n2,0 -> 2,2\n1,1 -> 1,1")).unwrap();
+        let mut image = Vec::new();
+        grid.write_heatmap(&mut image).unwrap();
+
+        // the grid spans from 1,0 to 3,2 and its hottest points have two vents
+        let mut expected = b"P5\n3 3\n255\n".to_vec();
+        expected.extend([0, 127, 0, 255, 255, 127, 0, 127, 0]);
+        assert_eq!(image, expected);
+
+        assert_eq!(
+            grid.hottest(3),
+            [
+                (Vector2::new(1, 1), 2),
+                (Vector2::new(2, 1), 2),
+                (Vector2::new(2, 0), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn heatmap_of_sparse_grid() {
+        let grid = render(&segments("0,0 -> 1,0\n0,5000000 -> 1,5000000")).unwrap();
+        assert!(grid.write_heatmap(&mut Vec::new()).is_err());
+        assert_eq!(grid.hottest(1)[0].1, 1);
+    }
+
+    #[tokio::test]
+    async fn grid_spanning_all_coordinates() {
+        let grid = render(&segments(
+            "-2147483648,-2147483648 -> -2147483647,-2147483648\n\
+             2147483647,2147483647 -> 2147483647,2147483646",
+        ))
+        .unwrap();
+        assert!(matches!(grid, Grid::Sparse(_)));
+        assert_eq!(grid.overlaps(), 0);
+
+        // a single line across all coordinates is too long for any grid
+        let input = "-2000000000,0 -> 2000000000,0\n";
+        let error = execute(lines(input), &Options::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The vents cover 4000000001 points, which are too many to render them, try --vents-analytic"
+        );
+        let options = Options {
+            vents_analytic: true,
+            ..Default::default()
+        };
+        assert_eq!(execute(lines(input), &options).await.unwrap(), [0, 0]);
+    }
+
+    proptest! {
+        #[test]
+        fn analytic_matches_rendered(seed: u64, size in 0usize..40, scale in 1u32..30) {
+            let options = GeneratorOptions { seed, size, scale: Some(scale) };
+            let mut input = Vec::new();
+            generate(&mut ChaCha8Rng::seed_from_u64(seed), &options, &mut input).unwrap();
+            let segments = segments(std::str::from_utf8(&input).unwrap());
+
+            prop_assert_eq!(count_analytic(&segments).unwrap(), render(&segments).unwrap().overlaps());
+        }
+    }
 }
@@ -1,33 +1,512 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
-use std::error::Error;
+use num_bigint::BigUint;
+use rand::Rng;
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Deref,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+// Options of the lanternfish. Not a doc comment, as it is flattened into the options of
+// the command line.
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// Also project the population of lanternfish of day 06 to this many days, which is
+    /// at most 10000 without a modulus
+    #[structopt(long)]
+    pub fish_days: Option<u64>,
+
+    /// Take the populations of day 06 modulo this number, usually a prime, instead of
+    /// calculating them exactly
+    #[structopt(long)]
+    pub fish_modulus: Option<u64>,
+
+    /// The timer of a lanternfish of day 06 after it has spawned a new one
+    #[structopt(long, default_value = "6")]
+    pub fish_reset: usize,
+
+    /// The timer of a newborn lanternfish of day 06
+    #[structopt(long, default_value = "8")]
+    pub fish_newborn: usize,
+
+    /// Export the daily population of day 06 as CSV to this file, and estimate the
+    /// growth rate
+    #[structopt(long, parse(from_os_str))]
+    pub fish_series: Option<PathBuf>,
+
+    /// The last day of the exported population of day 06, which is at most 10000
+    #[structopt(long, default_value = "256")]
+    pub fish_series_days: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            fish_days: None,
+            fish_modulus: None,
+            fish_reset: 6,
+            fish_newborn: 8,
+            fish_series: None,
+            fish_series_days: 256,
+        }
+    }
+}
+
+/// A square matrix of big integers, whose entries are optionally reduced by a modulus
+#[derive(Debug, Clone, PartialEq)]
+struct Matrix(Vec<Vec<BigUint>>);
+
+impl Matrix {
+    /// Create the identity matrix of the given size
+    fn identity(size: usize) -> Self {
+        Self(
+            (0..size)
+                .map(|i| (0..size).map(|j| BigUint::from((i == j) as u8)).collect())
+                .collect(),
+        )
+    }
+
+    /// Multiply the matrix with a vector
+    fn apply(&self, vector: &[BigUint], modulus: Option<&BigUint>) -> Vec<BigUint> {
+        self.0
+            .iter()
+            .map(|row| {
+                let sum = row.iter().zip(vector).map(|(a, b)| a * b).sum();
+                reduce(sum, modulus)
+            })
+            .collect()
+    }
+
+    /// Multiply the matrix with another one of the same size
+    fn mul(&self, other: &Self, modulus: Option<&BigUint>) -> Self {
+        let size = self.0.len();
+        Self(
+            self.0
+                .iter()
+                .map(|row| {
+                    (0..size)
+                        .map(|j| {
+                            let sum = (0..size).map(|k| &row[k] * &other.0[k][j]).sum();
+                            reduce(sum, modulus)
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Estimate the dominant eigenvalue of the matrix by power iteration. The iteration
+    /// runs on the matrix plus the identity, which has the same dominant eigenvector, but
+    /// doesn't oscillate for periodic matrices.
+    fn dominant_eigenvalue(&self) -> f64 {
+        let matrix: Vec<Vec<f64>> = self
+            .0
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|entry| u64::try_from(entry).map_or(f64::INFINITY, |entry| entry as f64))
+                    .collect()
+            })
+            .collect();
+
+        let mut vector = vec![1.0; matrix.len()];
+        let mut eigenvalue = 0.0;
+        for _ in 0..10000 {
+            let next: Vec<f64> = matrix
+                .iter()
+                .zip(&vector)
+                .map(|(row, own)| own + row.iter().zip(&vector).map(|(a, b)| a * b).sum::<f64>())
+                .collect();
+            let norm = next.iter().sum::<f64>();
+            let previous = std::mem::replace(&mut eigenvalue, norm / vector.iter().sum::<f64>());
+            vector = next.into_iter().map(|v| v / norm).collect();
+            if (eigenvalue - previous).abs() < 1e-12 {
+                break;
+            }
+        }
+
+        eigenvalue - 1.0
+    }
+
+    /// Raise the matrix to the given power by repeated squaring
+    fn pow(&self, mut exp: u64, modulus: Option<&BigUint>) -> Self {
+        let mut result = Self::identity(self.0.len());
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.mul(&base, modulus);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.mul(&base, modulus);
+            }
+        }
+        result
+    }
+}
+
+/// Helper to reduce a number by the modulus, if there is one
+fn reduce(value: BigUint, modulus: Option<&BigUint>) -> BigUint {
+    match modulus {
+        Some(modulus) => value % modulus,
+        None => value,
+    }
+}
+
+/// The life cycle of lanternfish, which spawn a new one, once their timer has passed 0
+#[derive(Debug, Clone, Copy)]
+struct Lifecycle {
+    /// The timer of a lanternfish after spawning
+    reset: usize,
+    /// The timer of a newborn lanternfish
+    newborn: usize,
+}
+
+impl Lifecycle {
+    /// Number of distinct timers of the lanternfish
+    fn timers(&self) -> usize {
+        self.reset.max(self.newborn) + 1
+    }
+
+    /// Create the matrix, which grows a histogram of the timers by one day. Each column
+    /// holds the lanternfish of the next day, which come from one with that timer.
+    fn transition(&self) -> Matrix {
+        let timers = self.timers();
+        let mut matrix = vec![vec![BigUint::default(); timers]; timers];
+        for timer in 1..timers {
+            matrix[timer - 1][timer] += 1u8;
+        }
+        matrix[self.reset][0] += 1u8;
+        matrix[self.newborn][0] += 1u8;
+        Matrix(matrix)
+    }
+}
+
+/// Grow the population of lanternfish, given as histogram of their timers, by some days
+fn grow(
+    lifecycle: &Lifecycle,
+    population: &[BigUint],
+    days: u64,
+    modulus: Option<&BigUint>,
+) -> Vec<BigUint> {
+    lifecycle
+        .transition()
+        .pow(days, modulus)
+        .apply(population, modulus)
+}
+
+/// Helper to get the total of a population
+fn total(population: &[BigUint], modulus: Option<&BigUint>) -> BigUint {
+    reduce(population.iter().sum(), modulus)
+}
+
+/// The last day of an exported series, whose rows grow with the population and would
+/// fill the disk otherwise
+const MAX_SERIES_DAYS: u64 = 10_000;
+
+/// The last day of an exact projection, whose population has thousands of digits
+/// already and grows out of bounds beyond
+const MAX_EXACT_DAYS: u64 = 10_000;
+
+/// Write the population of each day up to the horizon as CSV
+fn write_series(
+    lifecycle: &Lifecycle,
+    population: &[BigUint],
+    horizon: u64,
+    modulus: Option<&BigUint>,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let timers: Vec<_> = (0..lifecycle.timers())
+        .map(|timer| format!(",timer{}", timer))
+        .collect();
+    writeln!(output, "day,total{}", timers.concat())?;
+
+    let transition = lifecycle.transition();
+    let mut population = population.to_vec();
+    for day in 0..=horizon {
+        if day > 0 {
+            population = transition.apply(&population, modulus);
+        }
+        let buckets: Vec<_> = population
+            .iter()
+            .map(|count| format!(",{}", count))
+            .collect();
+        writeln!(
+            output,
+            "{},{}{}",
+            day,
+            total(&population, modulus),
+            buckets.concat()
+        )?;
+    }
+
+    Ok(())
+}
 
 /// Executes the exercise of day 6
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
+    let lifecycle = Lifecycle {
+        reset: options.fish_reset,
+        newborn: options.fish_newborn,
+    };
+    let modulus = match options.fish_modulus {
+        Some(0) => return Err("The modulus of the lanternfish must not be 0".into()),
+        modulus => modulus.map(BigUint::from),
+    };
+    let modulus = modulus.as_ref();
+    match options.fish_days {
+        Some(days) if days > MAX_EXACT_DAYS && modulus.is_none() => {
+            return Err(format!(
+                "The lanternfish can be projected for up to {} days without a modulus, not {}",
+                MAX_EXACT_DAYS, days
+            )
+            .into())
+        }
+        _ => {}
+    }
+
     // read in initial population
-    let mut population = [0u64; 9];
-    input
-        .try_next()
-        .await?
-        .unwrap()
-        .split(',')
-        .map(|lf| lf.parse().unwrap())
-        .for_each(|timer: usize| population[timer] += 1);
+    let mut population = vec![BigUint::default(); lifecycle.timers()];
+    let line = input.try_next().await?.ok_or("Missing lanternfish")?;
+    for timer in line.split(',') {
+        let timer: usize = timer.parse()?;
+        *population
+            .get_mut(timer)
+            .ok_or_else(|| format!("Lanternfish with invalid timer {}", timer))? += 1u8;
+    }
+    let population: Vec<_> = population
+        .into_iter()
+        .map(|count| reduce(count, modulus))
+        .collect();
+
+    // project the population as far as requested
+    if let Some(days) = options.fish_days {
+        let projected = grow(&lifecycle, &population, days, modulus);
+        println!(
+            "Population of lanternfish after {} days: {}",
+            days,
+            total(&projected, modulus)
+        );
+    }
+
+    // export the daily population and estimate its growth rate
+    if let Some(path) = &options.fish_series {
+        let horizon = options.fish_series_days;
+        if horizon > MAX_SERIES_DAYS {
+            return Err(format!(
+                "The series of lanternfish can be exported for up to {} days, not {}",
+                MAX_SERIES_DAYS, horizon
+            )
+            .into());
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        write_series(&lifecycle, &population, horizon, modulus, &mut file)?;
+        file.flush()?;
+        println!(
+            "Population of lanternfish grows by a factor of {:.6} per day",
+            lifecycle.transition().dominant_eigenvalue()
+        );
+    }
+
+    // grow population over time and capture total population after 80 and 256 days
+    let eighty_days = grow(&lifecycle, &population, 80, modulus);
+    let all_days = grow(&lifecycle, &eighty_days, 256 - 80, modulus);
+    let checked = |population: &[BigUint]| {
+        let total = total(population, modulus);
+        u64::try_from(&total).map_err(|_| {
+            format!(
+                "The population of {} lanternfish does not fit into 64 bits",
+                total
+            )
+        })
+    };
 
-    // grow population over time
-    let mut eighty_days = 0;
-    for i in 0..256 {
-        population.rotate_left(1);
-        population[6] += population[8];
+    Ok([checked(&eighty_days)?, checked(&all_days)?])
+}
 
-        // capture total population after 80 days
-        if i == 79 {
-            eighty_days = population.iter().sum();
+/// Generate an initial population of `size` lanternfish
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let population: Vec<_> = (0..options.size)
+        .map(|_| rng.gen_range(1..=5).to_string())
+        .collect();
+    writeln!(output, "{}", population.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+    use proptest::{collection::vec, prelude::*};
+
+    /// Reference, which simulates each lanternfish on its own
+    fn simulate(lifecycle: &Lifecycle, mut fish: Vec<usize>, days: usize) -> usize {
+        for _ in 0..days {
+            let mut newborn = 0;
+            for timer in &mut fish {
+                if *timer == 0 {
+                    *timer = lifecycle.reset;
+                    newborn += 1;
+                } else {
+                    *timer -= 1;
+                }
+            }
+            fish.resize(fish.len() + newborn, lifecycle.newborn);
         }
+        fish.len()
+    }
+
+    /// Helper to get the histogram of the timers of some lanternfish
+    fn histogram(lifecycle: &Lifecycle, fish: &[usize]) -> Vec<BigUint> {
+        let mut population = vec![BigUint::default(); lifecycle.timers()];
+        fish.iter().for_each(|&timer| population[timer] += 1u8);
+        population
     }
 
-    Ok([eighty_days, population.iter().sum()])
+    const LIFECYCLE: Lifecycle = Lifecycle {
+        reset: 6,
+        newborn: 8,
+    };
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines("3,4,3,1,2"), &Options::default())
+                .await
+                .unwrap(),
+            [5934, 26984457539]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_timer() {
+        let options = Options {
+            fish_newborn: 4,
+            ..Default::default()
+        };
+        assert!(execute(lines("3,7"), &options).await.is_err());
+        assert!(execute(lines("3,6"), &options).await.is_ok());
+    }
+
+    #[test]
+    fn huge_number_of_days() {
+        // a day more or less must be the same as a single step of the transition
+        let modulus = BigUint::from(1_000_000_007u64);
+        let population = histogram(&LIFECYCLE, &[3, 4, 3, 1, 2]);
+        let before = grow(
+            &LIFECYCLE,
+            &population,
+            1_000_000_000_000 - 1,
+            Some(&modulus),
+        );
+        let after = grow(&LIFECYCLE, &population, 1_000_000_000_000, Some(&modulus));
+        assert_eq!(after, LIFECYCLE.transition().apply(&before, Some(&modulus)));
+    }
+
+    #[test]
+    fn series() {
+        let mut output = Vec::new();
+        let population = histogram(&LIFECYCLE, &[3, 4, 3, 1, 2]);
+        write_series(&LIFECYCLE, &population, 3, None, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+day,total,timer0,timer1,timer2,timer3,timer4,timer5,timer6,timer7,timer8
+0,5,0,1,1,2,1,0,0,0,0
+1,5,1,1,2,1,0,0,0,0,0
+2,6,1,2,1,0,0,0,1,0,1
+3,7,2,1,0,0,0,1,1,1,1
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn series_horizon() {
+        // the series has its own horizon, far projections are fine nevertheless
+        let options = Options {
+            fish_days: Some(1_000_000_000_000),
+            fish_modulus: Some(1_000_000_007),
+            fish_series: Some(std::env::temp_dir().join("unused.csv")),
+            fish_series_days: 10_001,
+            ..Options::default()
+        };
+        let error = execute(lines("3,4,3,1,2"), &options).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The series of lanternfish can be exported for up to 10000 days, not 10001"
+        );
+    }
+
+    #[tokio::test]
+    async fn exact_horizon() {
+        let options = Options {
+            fish_days: Some(10_001),
+            ..Options::default()
+        };
+        let error = execute(lines("3,4,3,1,2"), &options).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The lanternfish can be projected for up to 10000 days without a modulus, not 10001"
+        );
+
+        let options = Options {
+            fish_days: Some(10_000),
+            ..Options::default()
+        };
+        assert_eq!(
+            execute(lines("3,4,3,1,2"), &options).await.unwrap(),
+            [5934, 26984457539]
+        );
+    }
+
+    #[test]
+    fn dominant_eigenvalue() {
+        // the growth rate solves 1 = x^-7 + x^-9, as a fish spawns after 7 and 9 days
+        let rate = LIFECYCLE.transition().dominant_eigenvalue();
+        assert!((rate.powi(-7) + rate.powi(-9) - 1.0).abs() < 1e-9);
+
+        // fish, which spawn every other day, double every other day
+        let lifecycle = Lifecycle {
+            reset: 1,
+            newborn: 1,
+        };
+        let rate = lifecycle.transition().dominant_eigenvalue();
+        assert!((rate - 2f64.sqrt()).abs() < 1e-9);
+    }
+
+    proptest! {
+        #[test]
+        fn grow_matches_reference(
+            // short cycles grow too fast for the reference
+            reset in 2usize..8,
+            newborn in 2usize..10,
+            fish in vec(0usize..10, 1..10),
+            days in 0usize..40,
+            modulus in 1u64..1000,
+        ) {
+            let lifecycle = Lifecycle { reset, newborn };
+            let fish: Vec<_> = fish.into_iter().map(|timer| timer % lifecycle.timers()).collect();
+            let population = histogram(&lifecycle, &fish);
+            let expected = simulate(&lifecycle, fish, days);
+
+            let exact = grow(&lifecycle, &population, days as u64, None);
+            prop_assert_eq!(total(&exact, None), BigUint::from(expected));
+
+            let modulus = BigUint::from(modulus);
+            let reduced = grow(&lifecycle, &population, days as u64, Some(&modulus));
+            prop_assert_eq!(total(&reduced, Some(&modulus)), expected % modulus);
+        }
+    }
 }
@@ -1,64 +1,513 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
-use std::error::Error;
+use rand::Rng;
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    error::Error,
+    fmt,
+    io::{self, Write},
+    ops::Deref,
+    str::FromStr,
+};
+use structopt::StructOpt;
 
-/// Executes the exercise of day 7
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
-) -> Result<[u64; 2], Box<dyn Error>> {
-    pin_mut!(input);
+// Options of the crab alignment. Not a doc comment, as it is flattened into the options
+// of the command line.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    /// Also align the crabs of day 07 with these fuel costs: linear, triangular, quadratic
+    /// or capped:<n>, whose cost per step grows like triangular up to n
+    #[structopt(long)]
+    pub crab_cost: Vec<Cost>,
 
-    // read in initial crab positions
-    let crabs: Vec<i32> = input
-        .try_next()
-        .await?
+    /// Print where the crabs of day 07 align with the fuel costs of both parts
+    #[structopt(long)]
+    pub crab_positions: bool,
+}
+
+/// The fuel a crab needs to move some distance, which must be convex in the distance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cost {
+    /// One fuel per step
+    Linear,
+    /// Each step costs one more fuel than the step before
+    Triangular,
+    /// The square of the distance
+    Quadratic,
+    /// Like triangular, but no step costs more than the cap
+    Capped(i64),
+}
+
+impl Cost {
+    /// Get the fuel to move the given distance
+    fn fuel(&self, distance: i64) -> i64 {
+        let triangular = |d: i64| d * (d + 1) / 2;
+        match *self {
+            Self::Linear => distance,
+            Self::Triangular => triangular(distance),
+            Self::Quadratic => distance * distance,
+            Self::Capped(cap) if distance <= cap => triangular(distance),
+            Self::Capped(cap) => triangular(cap) + (distance - cap) * cap,
+        }
+    }
+}
+
+impl FromStr for Cost {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "triangular" => Ok(Self::Triangular),
+            "quadratic" => Ok(Self::Quadratic),
+            _ => match s.strip_prefix("capped:").map(str::parse) {
+                Some(Ok(cap)) if cap >= 0 => Ok(Self::Capped(cap)),
+                _ => Err(format!("Invalid fuel cost {:?}", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Cost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Linear => write!(f, "linear"),
+            Self::Triangular => write!(f, "triangular"),
+            Self::Quadratic => write!(f, "quadratic"),
+            Self::Capped(cap) => write!(f, "capped:{}", cap),
+        }
+    }
+}
+
+/// The cheapest position to align the crabs and the fuel it takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Alignment {
+    position: i64,
+    fuel: i64,
+}
+
+/// Helper to get the total fuel to align all crabs at the given position
+fn total_fuel(crabs: &[i64], position: i64, cost: Cost) -> i64 {
+    crabs
+        .iter()
+        .map(|&crab| cost.fuel((crab - position).abs()))
+        .sum()
+}
+
+/// Find the cheapest alignment of the crabs among the given positions, the leftmost one
+/// if there are several
+fn cheapest(crabs: &[i64], positions: impl Iterator<Item = i64>, cost: Cost) -> Alignment {
+    positions
+        .map(|position| Alignment {
+            position,
+            fuel: total_fuel(crabs, position, cost),
+        })
+        .min_by_key(|alignment| (alignment.fuel, alignment.position))
         .unwrap()
-        .split(',')
-        .map(|lf| lf.parse().unwrap())
+}
+
+/// Find the cheapest position to align the crabs, which must not be empty. If several
+/// positions are equally cheap, the leftmost one is taken.
+fn align(crabs: &[i64], cost: Cost) -> Alignment {
+    let (min, max) = (*crabs.iter().min().unwrap(), *crabs.iter().max().unwrap());
+    match cost {
+        // the lower median minimizes the sum of distances
+        Cost::Linear => {
+            let mut sorted = crabs.to_vec();
+            sorted.sort_unstable();
+            let median = sorted[(sorted.len() - 1) / 2];
+            Alignment {
+                position: median,
+                fuel: total_fuel(crabs, median, cost),
+            }
+        }
+
+        // the optimum is less than half a step away from the mean
+        Cost::Triangular => {
+            let len = crabs.len() as i64;
+            let mean = crabs.iter().sum::<i64>().div_euclid(len);
+            cheapest(crabs, (mean - 1).max(min)..=(mean + 2).min(max), cost)
+        }
+
+        // the total fuel is convex, so ternary search narrows down the optimum
+        Cost::Quadratic | Cost::Capped(_) => {
+            let (mut lo, mut hi) = (min, max);
+            while hi - lo > 2 {
+                let third = (hi - lo) / 3;
+                let (m1, m2) = (lo + third, hi - third);
+                if total_fuel(crabs, m1, cost) <= total_fuel(crabs, m2, cost) {
+                    hi = m2;
+                } else {
+                    lo = m1 + 1;
+                }
+            }
+            cheapest(crabs, lo..=hi, cost)
+        }
+    }
+}
+
+/// The cheapest point to align crabs on a lattice and the fuel it takes
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Meeting {
+    point: Vec<i64>,
+    fuel: i64,
+}
+
+impl fmt::Display for Meeting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let point: Vec<_> = self.point.iter().map(i64::to_string).collect();
+        write!(f, "{} takes {} fuel", point.join(","), self.fuel)
+    }
+}
+
+/// Helper to get the total fuel to align all crabs on a lattice at the given point. Each
+/// crab pays the fuel of its Manhattan distance to the point.
+fn lattice_fuel(crabs: &[Vec<i64>], point: &[i64], cost: Cost) -> i64 {
+    crabs
+        .iter()
+        .map(|crab| {
+            let distance = crab.iter().zip(point).map(|(c, p)| (c - p).abs()).sum();
+            cost.fuel(distance)
+        })
+        .sum()
+}
+
+/// Find the cheapest point to align crabs on a lattice, where each crab pays the fuel of
+/// its Manhattan distance. For linear cost, the fuel of the axes adds up, so each axis is
+/// aligned on its own. Other costs don't split per axis, so the lattice is searched by
+/// branch and bound, starting from the per axis optimum.
+fn meet(crabs: &[Vec<i64>], cost: Cost) -> Meeting {
+    let axes = crabs[0].len();
+    let point: Vec<_> = (0..axes)
+        .map(|axis| {
+            let coords: Vec<_> = crabs.iter().map(|crab| crab[axis]).collect();
+            align(&coords, cost).position
+        })
+        .collect();
+    let mut best = Meeting {
+        fuel: lattice_fuel(crabs, &point, cost),
+        point,
+    };
+    if axes == 1 || cost == Cost::Linear {
+        return best;
+    }
+
+    // no crab gets closer outside of the bounding box of all crabs. The fuel within a box
+    // is at least the fuel of each crab to its closest point of the box, as the cost grows
+    // with the distance. The total fuel is convex, so it is also bounded by the tangent
+    // at the center of the box.
+    let bound = |lo: &[i64], hi: &[i64]| -> i64 {
+        let closest: i64 = crabs
+            .iter()
+            .map(|crab| {
+                let distance = (0..axes)
+                    .map(|axis| (lo[axis] - crab[axis]).max(crab[axis] - hi[axis]).max(0))
+                    .sum();
+                cost.fuel(distance)
+            })
+            .sum();
+
+        let center: Vec<_> = (0..axes).map(|axis| (lo[axis] + hi[axis]) / 2).collect();
+        let mut slope = vec![0; axes];
+        for crab in crabs {
+            let distance: i64 = (0..axes)
+                .map(|axis| (crab[axis] - center[axis]).abs())
+                .sum();
+            if distance > 0 {
+                let step = cost.fuel(distance) - cost.fuel(distance - 1);
+                for axis in 0..axes {
+                    slope[axis] += step * (center[axis] - crab[axis]).signum();
+                }
+            }
+        }
+        let tangent = lattice_fuel(crabs, &center, cost)
+            + (0..axes)
+                .map(|axis| {
+                    (slope[axis] * (lo[axis] - center[axis]))
+                        .min(slope[axis] * (hi[axis] - center[axis]))
+                })
+                .sum::<i64>();
+
+        closest.max(tangent)
+    };
+    let lo: Vec<_> = (0..axes)
+        .map(|axis| crabs.iter().map(|crab| crab[axis]).min().unwrap())
+        .collect();
+    let hi: Vec<_> = (0..axes)
+        .map(|axis| crabs.iter().map(|crab| crab[axis]).max().unwrap())
         .collect();
 
-    // find aligned position with minimum total fuel consumption part1
-    let mut x = crabs.iter().sum::<i32>() / crabs.len() as i32;
-    let mut fuel = i32::MAX;
-    let lr_inv = crabs.len() as i32;
-    loop {
-        // calculate loss and update horizontal position using simple gradient descent
-        let grad: i32 = crabs.iter().map(|&c| -(c - x).signum() * x).sum();
-        x -= grad.signum() * (grad.abs() / lr_inv).max(1);
-
-        // calculate loss and update fuel or exit loop, if needed
-        let loss: i32 = crabs.iter().map(|&c| (c - x).abs()).sum();
-        if loss < fuel {
-            fuel = loss;
-        } else {
+    // split the most promising box along its longest axis, until the bound of all
+    // remaining boxes is no better than the best point
+    let mut boxes = BinaryHeap::from([Reverse((bound(&lo, &hi), lo, hi))]);
+    while let Some(Reverse((fuel, lo, hi))) = boxes.pop() {
+        if fuel >= best.fuel {
             break;
         }
+        if lo == hi {
+            // the bound of a single point is its fuel
+            best = Meeting { point: lo, fuel };
+            continue;
+        }
+
+        let axis = (0..axes).max_by_key(|&axis| hi[axis] - lo[axis]).unwrap();
+        let mid = lo[axis] + (hi[axis] - lo[axis]) / 2;
+        let (mut left, mut right) = (hi.clone(), lo.clone());
+        left[axis] = mid;
+        right[axis] = mid + 1;
+        for (lo, hi) in [(lo, left), (right, hi)] {
+            let fuel = bound(&lo, &hi);
+            if fuel < best.fuel {
+                boxes.push(Reverse((fuel, lo, hi)));
+            }
+        }
     }
+    best
+}
 
-    // find aligned position with minimum total fuel consumption part2
-    let mut fuel2 = i32::MAX;
-    let lr_inv = (crabs.len() * crabs.len()) as i32;
-    loop {
-        // calculate loss and update horizontal position using simple gradient descent
-        let grad: i32 = -crabs
-            .iter()
-            .map(|&c| (c - x).signum() * x * (c - x).abs())
-            .sum::<i32>()
-            / 2;
-        x -= grad.signum() * (grad.abs() / lr_inv).max(1);
+/// Read the crabs, either as horizontal positions in a single comma separated line, or as
+/// comma separated coordinates on a lattice, which are separated by whitespace or lines
+fn parse_crabs(lines: &[impl Deref<Target = str>]) -> Result<Vec<Vec<i64>>, Box<dyn Error>> {
+    let parse = |crab: &str| {
+        crab.split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<i64>, _>>()
+    };
 
-        // calculate loss and update fuel or exit loop, if needed
-        let loss = crabs
+    // blank lines, like a trailing one, separate nothing
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let crabs = match lines.as_slice() {
+        [line] if !line.contains(char::is_whitespace) => {
+            parse(line)?.into_iter().map(|x| vec![x]).collect()
+        }
+        lines => lines
             .iter()
-            .map(|&c| (c - x).abs())
-            .map(|d| d * (d + 1))
-            .sum::<i32>()
-            / 2;
-        if loss < fuel2 {
-            fuel2 = loss;
-        } else {
-            break;
+            .flat_map(|line| line.split_whitespace())
+            .map(parse)
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    // all crabs need to be on the same lattice
+    match crabs.first() {
+        None => Err("Missing crabs".into()),
+        Some(first) if crabs.iter().any(|crab| crab.len() != first.len()) => {
+            Err("All crabs need the same number of coordinates".into())
+        }
+        Some(_) => Ok(crabs),
+    }
+}
+
+/// Executes the exercise of day 7
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
+) -> Result<[u64; 2], Box<dyn Error>> {
+    pin_mut!(input);
+
+    // read in initial crab positions
+    let lines: Vec<L> = input.try_collect().await?;
+    let crabs = parse_crabs(&lines)?;
+
+    // align the crabs with the costs requested in addition
+    for &cost in &options.crab_cost {
+        println!(
+            "Cheapest alignment with {} fuel cost at {}",
+            cost,
+            meet(&crabs, cost)
+        );
+    }
+
+    // report where the crabs of both parts align, if requested
+    let costs = [Cost::Linear, Cost::Triangular];
+    let meetings = costs.map(|cost| meet(&crabs, cost));
+    if options.crab_positions {
+        for (cost, meeting) in costs.iter().zip(&meetings) {
+            println!("Cheapest alignment with {} fuel cost at {}", cost, meeting);
         }
     }
 
-    Ok([fuel as u64, fuel2 as u64])
+    Ok([meetings[0].fuel as u64, meetings[1].fuel as u64])
+}
+
+/// Generate `size` crabs with horizontal positions below `scale`
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let scale = options.scale.unwrap_or(2000).max(1);
+    let crabs: Vec<_> = (0..options.size)
+        .map(|_| rng.gen_range(0..scale).to_string())
+        .collect();
+    writeln!(output, "{}", crabs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+    use proptest::{collection::vec, prelude::*};
+
+    /// Reference, which tries out every position between the crabs
+    fn align_exhaustive(crabs: &[i64], cost: Cost) -> Alignment {
+        let (min, max) = (*crabs.iter().min().unwrap(), *crabs.iter().max().unwrap());
+        cheapest(crabs, min..=max, cost)
+    }
+
+    /// Strategy to pick one of the fuel costs
+    fn costs() -> impl Strategy<Value = Cost> {
+        prop_oneof![
+            Just(Cost::Linear),
+            Just(Cost::Triangular),
+            Just(Cost::Quadratic),
+            (0i64..20).prop_map(Cost::Capped),
+        ]
+    }
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines("16,1,2,0,4,2,7,1,2,14"), &Options::default())
+                .await
+                .unwrap(),
+            [37, 168]
+        );
+
+        // a trailing blank line still leaves the crabs on a line
+        assert_eq!(
+            execute(lines("16,1,2,0,4,2,7,1,2,14\n\n"), &Options::default())
+                .await
+                .unwrap(),
+            [37, 168]
+        );
+    }
+
+    #[test]
+    fn example_positions() {
+        let crabs = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+        assert_eq!(
+            align(&crabs, Cost::Linear),
+            Alignment {
+                position: 2,
+                fuel: 37
+            }
+        );
+        assert_eq!(
+            align(&crabs, Cost::Triangular),
+            Alignment {
+                position: 5,
+                fuel: 168
+            }
+        );
+    }
+
+    #[test]
+    fn capped_cost() {
+        let cost = Cost::Capped(3);
+        let fuel: Vec<_> = (0..6).map(|distance| cost.fuel(distance)).collect();
+        assert_eq!(fuel, [0, 1, 3, 6, 9, 12]);
+
+        assert_eq!("capped:3".parse(), Ok(cost));
+        assert_eq!(cost.to_string(), "capped:3");
+        assert!("capped:-1".parse::<Cost>().is_err());
+        assert!("cubic".parse::<Cost>().is_err());
+    }
+
+    #[tokio::test]
+    async fn lattice() {
+        let input = "0,0,0 4,0,1\n2,6,1\n";
+        assert_eq!(
+            execute(lines(input), &Options::default()).await.unwrap(),
+            [3 + 2 + 6, 6 + 3 + 21]
+        );
+
+        let crabs = parse_crabs(&[input.lines().collect::<Vec<_>>().join("\n")]).unwrap();
+        assert_eq!(
+            meet(&crabs, Cost::Linear),
+            Meeting {
+                point: vec![2, 0, 1],
+                fuel: 11
+            }
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let parse = |lines: &[&str]| {
+            parse_crabs(
+                &lines
+                    .iter()
+                    .map(|line| line.to_string())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(parse(&["1,2,3"]).unwrap(), [[1], [2], [3]]);
+        assert_eq!(parse(&["1,2"]).unwrap(), [[1], [2]]);
+        assert_eq!(parse(&["1,2 "]).unwrap(), [[1], [2]]);
+        assert_eq!(parse(&["1,2 3,4"]).unwrap(), [[1, 2], [3, 4]]);
+        assert_eq!(parse(&["1,2", "3,4"]).unwrap(), [[1, 2], [3, 4]]);
+        assert_eq!(parse(&["1,2", " ", ""]).unwrap(), [[1], [2]]);
+        assert!(parse(&["1,2 3"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn meet_matches_reference(
+            crabs in vec((-10i64..10, -10i64..10), 1..10),
+            cost in costs(),
+        ) {
+            let mut best: Option<Meeting> = None;
+            for x in -10..10 {
+                for y in -10..10 {
+                    let fuel = crabs
+                        .iter()
+                        .map(|&(cx, cy)| cost.fuel((cx - x).abs() + (cy - y).abs()))
+                        .sum();
+                    if best.as_ref().is_none_or(|best| fuel < best.fuel) {
+                        best = Some(Meeting { point: vec![x, y], fuel });
+                    }
+                }
+            }
+
+            let crabs: Vec<_> = crabs.into_iter().map(|(x, y)| vec![x, y]).collect();
+            prop_assert_eq!(meet(&crabs, cost).fuel, best.unwrap().fuel);
+        }
+
+        #[test]
+        fn meet_matches_reference_in_space(
+            crabs in vec((-4i64..4, -4i64..4, -4i64..4), 1..8),
+            cost in costs(),
+        ) {
+            let mut best = i64::MAX;
+            for x in -4..4 {
+                for y in -4..4 {
+                    for z in -4..4 {
+                        let fuel = crabs
+                            .iter()
+                            .map(|&(cx, cy, cz)| {
+                                cost.fuel((cx - x).abs() + (cy - y).abs() + (cz - z).abs())
+                            })
+                            .sum();
+                        best = best.min(fuel);
+                    }
+                }
+            }
+
+            let crabs: Vec<_> = crabs.into_iter().map(|(x, y, z)| vec![x, y, z]).collect();
+            let meeting = meet(&crabs, cost);
+            prop_assert_eq!(meeting.fuel, best);
+            prop_assert_eq!(lattice_fuel(&crabs, &meeting.point, cost), best);
+        }
+
+        #[test]
+        fn align_matches_reference(crabs in vec(-100i64..100, 1..20), cost in costs()) {
+            prop_assert_eq!(align(&crabs, cost), align_exhaustive(&crabs, cost));
+        }
+    }
 }
@@ -1,130 +1,641 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
-use std::{collections::HashMap, error::Error};
-
-/// Helper to determine if a pattern contains another pattern
-#[inline]
-fn contains_pattern(a: &str, b: &str) -> bool {
-    for char in b.chars() {
-        if !a.contains(char) {
-            return false;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use snafu::{OptionExt, Snafu};
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    ops::Deref,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+// Options of the displays. Not a doc comment, as it is flattened into the options of the
+// command line.
+#[derive(Debug, Default, Clone, StructOpt)]
+pub struct Options {
+    /// Decode the displays of day 08 with the definition of this file instead of seven
+    /// segment digits. Each line holds a symbol and its lit segments, like `1 cf`.
+    #[structopt(long, parse(from_os_str))]
+    pub display: Option<PathBuf>,
+}
+
+/// Scramble the wires of a display and show some symbols on it, like the displays of
+/// day 08
+#[derive(Debug, Clone, StructOpt)]
+pub struct ScrambleOptions {
+    /// The symbols to show on the scrambled display
+    symbols: String,
+
+    /// The wire of each segment, like `deafgbc` for seven segments, random if not given
+    #[structopt(long)]
+    wires: Option<String>,
+
+    /// Seed of the random wiring and of the order of the patterns
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    #[structopt(flatten)]
+    display: Options,
+}
+
+/// The errors of undecodable lines
+#[derive(Debug, Snafu)]
+enum DecodeError {
+    #[snafu(display("Invalid pattern {:?} in line {}", pattern, line))]
+    InvalidPattern { line: usize, pattern: String },
+
+    #[snafu(display("Missing separator of the shown patterns in line {}", line))]
+    MissingSeparator { line: usize },
+
+    #[snafu(display("No wiring of the display shows the patterns of line {}", line))]
+    Inconsistent { line: usize },
+
+    #[snafu(display("The patterns of line {} show {} or {}", line, first, second))]
+    Ambiguous {
+        line: usize,
+        first: String,
+        second: String,
+    },
+
+    #[snafu(display(
+        "The sum of the readings up to line {} does not fit into 64 bits",
+        line
+    ))]
+    Overflow { line: usize },
+}
+
+/// The lit segments of each digit on an unscrambled display
+const DIGITS: [&str; 10] = [
+    "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
+];
+
+/// A display, which shows each of its symbols by lighting up a set of its segments. The
+/// segments are named by the letters from `a` on and stored as bit masks.
+#[derive(Debug, Clone, PartialEq)]
+struct Display {
+    segments: usize,
+    glyphs: Vec<(char, u32)>,
+}
+
+impl Display {
+    /// The display of the puzzle, which shows the digits on seven segments
+    fn seven_segment() -> Self {
+        let definition: Vec<_> = DIGITS
+            .iter()
+            .enumerate()
+            .map(|(digit, segments)| format!("{} {}", digit, segments))
+            .collect();
+        Self::parse(&definition.join("\n")).unwrap()
+    }
+
+    /// Read the definition of a display, which holds one symbol and its lit segments per
+    /// line. The display has as many segments as letters are used.
+    fn parse(definition: &str) -> Result<Self, String> {
+        let mut glyphs = Vec::new();
+        for line in definition.lines().filter(|line| !line.trim().is_empty()) {
+            let invalid = || format!("Invalid glyph {:?} of the display", line);
+            let mut split = line.split_whitespace();
+            let mut symbol = split.next().ok_or_else(invalid)?.chars();
+            let symbol = symbol.next().filter(|_| symbol.next().is_none());
+            let mask = split
+                .next()
+                .map(|segments| mask(segments, 32))
+                .unwrap_or(Some(0));
+            match (symbol, mask, split.next()) {
+                (Some(symbol), Some(mask), None) => glyphs.push((symbol, mask)),
+                _ => return Err(invalid()),
+            }
+        }
+
+        // all glyphs must differ, or they could not be told apart
+        for (i, &(symbol, mask)) in glyphs.iter().enumerate() {
+            if let Some((other, _)) = glyphs[..i]
+                .iter()
+                .find(|&&(other, m)| other == symbol || m == mask)
+            {
+                return Err(format!(
+                    "The glyphs {} and {} of the display clash",
+                    other, symbol
+                ));
+            }
+        }
+
+        let segments = glyphs.iter().fold(0, |all, (_, mask)| all | mask);
+        Ok(Self {
+            segments: (u32::BITS - segments.leading_zeros()) as usize,
+            glyphs,
+        })
+    }
+
+    /// Find all readings of the shown patterns, so each observed pattern is a distinct
+    /// glyph and the wires can be connected to distinct segments. The glyphs of the shown
+    /// patterns and a fitting wiring, the segment of each wire, are passed to `visit`,
+    /// until it returns false.
+    fn readings(
+        &self,
+        observed: &[u32],
+        shown: &[u32],
+        visit: &mut dyn FnMut(&[usize], &[usize]) -> bool,
+    ) {
+        let all = (1u32 << self.segments) - 1;
+        let patterns: Vec<_> = observed.iter().chain(shown).copied().collect();
+        let mut glyphs = Vec::new();
+        self.assign(
+            &patterns,
+            observed.len(),
+            vec![all; self.segments],
+            &mut glyphs,
+            visit,
+        );
+    }
+
+    /// Assign a glyph to the next pattern and narrow down the segments each wire can be
+    /// connected to. The first `distinct` patterns need distinct glyphs. Returns false,
+    /// once the search is stopped.
+    fn assign(
+        &self,
+        patterns: &[u32],
+        distinct: usize,
+        candidates: Vec<u32>,
+        glyphs: &mut Vec<usize>,
+        visit: &mut dyn FnMut(&[usize], &[usize]) -> bool,
+    ) -> bool {
+        let pattern = match patterns.get(glyphs.len()) {
+            Some(&pattern) => pattern,
+            None => {
+                return match connect(&candidates) {
+                    Some(wiring) => visit(&glyphs[distinct..], &wiring),
+                    None => true,
+                }
+            }
+        };
+
+        for (index, &(_, glyph)) in self.glyphs.iter().enumerate() {
+            if glyph.count_ones() != pattern.count_ones()
+                || (glyphs.len() < distinct && glyphs.contains(&index))
+            {
+                continue;
+            }
+
+            // lit wires go to lit segments, dark wires to dark ones
+            let narrowed: Vec<_> = candidates
+                .iter()
+                .enumerate()
+                .map(|(wire, &segments)| {
+                    if pattern & (1 << wire) != 0 {
+                        segments & glyph
+                    } else {
+                        segments & !glyph
+                    }
+                })
+                .collect();
+            if narrowed.contains(&0) {
+                continue;
+            }
+
+            glyphs.push(index);
+            let proceed = self.assign(patterns, distinct, narrowed, glyphs, visit);
+            glyphs.pop();
+            if !proceed {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Decode the shown patterns of a line, which must read the same with all wirings.
+    /// Returns the reading and the segment of each wire of a fitting wiring.
+    fn decode(
+        &self,
+        line: usize,
+        observed: &[u32],
+        shown: &[u32],
+    ) -> Result<(String, Vec<usize>), DecodeError> {
+        let mut decoded: Option<(String, Vec<usize>)> = None;
+        let mut ambiguous = None;
+        self.readings(observed, shown, &mut |glyphs, wiring| {
+            let reading: String = glyphs.iter().map(|&glyph| self.glyphs[glyph].0).collect();
+            match &decoded {
+                Some((first, _)) if *first != reading => {
+                    ambiguous = Some(reading);
+                    false
+                }
+                Some(_) => true,
+                None => {
+                    decoded = Some((reading, wiring.to_vec()));
+                    true
+                }
+            }
+        });
+
+        match (decoded, ambiguous) {
+            (None, _) => Inconsistent { line }.fail(),
+            (Some((first, _)), Some(second)) => Ambiguous {
+                line,
+                first,
+                second,
+            }
+            .fail(),
+            (Some(decoded), None) => Ok(decoded),
+        }
+    }
+
+    /// Scramble the wires of the display at random. Returns the wire of each segment.
+    fn random_wires(&self, rng: &mut impl Rng) -> Vec<char> {
+        let mut wires: Vec<char> = ('a'..).take(self.segments).collect();
+        wires.shuffle(rng);
+        wires
+    }
+
+    /// Read the wire of each segment, like `deafgbc` for a seven segment display
+    fn parse_wires(&self, wires: &str) -> Result<Vec<char>, String> {
+        let wires: Vec<char> = wires.chars().collect();
+        let mut sorted = wires.clone();
+        sorted.sort_unstable();
+        if sorted.into_iter().ne(('a'..).take(self.segments)) {
+            return Err(format!(
+                "The wiring {:?} does not connect each of the {} segments to a distinct wire",
+                wires.iter().collect::<String>(),
+                self.segments
+            ));
         }
+        Ok(wires)
+    }
+
+    /// Show a symbol on a display with the given wires in random wire order
+    fn show(&self, rng: &mut impl Rng, wires: &[char], symbol: char) -> Option<String> {
+        let &(_, glyph) = self.glyphs.iter().find(|&&(s, _)| s == symbol)?;
+        let mut pattern: Vec<_> = (0..self.segments)
+            .filter(|segment| glyph & (1 << segment) != 0)
+            .map(|segment| wires[segment])
+            .collect();
+        pattern.shuffle(rng);
+        Some(pattern.into_iter().collect())
     }
 
-    true
+    /// Encode a line of the puzzle, which observes all symbols of the display in random
+    /// order and shows the given symbols with the given wires
+    fn encode(&self, rng: &mut impl Rng, wires: &[char], symbols: &str) -> Result<String, String> {
+        let mut observed: Vec<_> = self.glyphs.iter().map(|&(symbol, _)| symbol).collect();
+        observed.shuffle(rng);
+        let mut show = |symbols: &mut dyn Iterator<Item = char>| {
+            symbols
+                .map(|symbol| {
+                    self.show(rng, wires, symbol)
+                        .ok_or_else(|| format!("The display can't show {:?}", symbol))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let observed = show(&mut observed.into_iter())?;
+        let shown = show(&mut symbols.chars())?;
+        Ok(format!("{} | {}", observed.join(" "), shown.join(" ")))
+    }
+
+    /// Check, if the symbol is the only one with its number of lit segments
+    fn is_unique(&self, symbol: char) -> bool {
+        let lit = |symbol| {
+            self.glyphs
+                .iter()
+                .find(|&&(s, _)| s == symbol)
+                .map(|(_, glyph)| glyph.count_ones())
+        };
+        let count = lit(symbol);
+        self.glyphs
+            .iter()
+            .filter(|&&(other, _)| lit(other) == count)
+            .count()
+            == 1
+    }
 }
 
-/// Get the number of common characters of two patterns
-#[inline]
-fn common_character_count(a: &str, b: &str) -> usize {
-    let mut res = 0;
-    for char in b.chars() {
-        if a.contains(char) {
-            res += 1;
+/// Connect each wire to a distinct segment among its candidates by finding a matching
+/// with augmenting paths. Returns the segment of each wire, if there is such a wiring.
+fn connect(candidates: &[u32]) -> Option<Vec<usize>> {
+    /// Try to connect the wire, possibly by moving other wires to another segment
+    fn augment(
+        candidates: &[u32],
+        wire: usize,
+        visited: &mut u32,
+        wires: &mut [Option<usize>],
+    ) -> bool {
+        for segment in 0..wires.len() {
+            let bit = 1 << segment;
+            if candidates[wire] & bit == 0 || *visited & bit != 0 {
+                continue;
+            }
+            *visited |= bit;
+            let free = match wires[segment] {
+                Some(other) => augment(candidates, other, visited, wires),
+                None => true,
+            };
+            if free {
+                wires[segment] = Some(wire);
+                return true;
+            }
         }
+        false
     }
-    res
+
+    // the wire connected to each segment
+    let mut wires = vec![None; candidates.len()];
+    for wire in 0..candidates.len() {
+        if !augment(candidates, wire, &mut 0, &mut wires) {
+            return None;
+        }
+    }
+
+    let mut wiring = vec![0; candidates.len()];
+    for (segment, wire) in wires.into_iter().enumerate() {
+        wiring[wire?] = segment;
+    }
+    Some(wiring)
+}
+
+/// Helper to turn the letters of a pattern into a bit mask, if they are among the first
+/// `segments` letters
+fn mask(pattern: &str, segments: usize) -> Option<u32> {
+    pattern.chars().try_fold(0, |mask, char| {
+        let bit = (char as u32).checked_sub('a' as u32)?;
+        (char.is_ascii_lowercase() && (bit as usize) < segments).then(|| mask | (1 << bit))
+    })
 }
 
 /// Executes the exercise of day 8
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
-    // read in the input pattern hist
-    let mut hist = [0u64; 10];
+    let display = match &options.display {
+        Some(path) => Display::parse(&fs::read_to_string(path)?)?,
+        None => Display::seven_segment(),
+    };
+
+    // decode the shown patterns of each line
+    let mut unique = 0u64;
     let mut sum = 0u64;
+    let mut number = 0;
     while let Some(line) = input.try_next().await? {
-        let split: Vec<_> = line.split(" | ").map(|p| p.to_owned()).collect();
-
-        // take the observed numbers and decode the pattern
-        let mut observed: HashMap<usize, Vec<String>> = HashMap::new();
-        for pattern in split[0].split_whitespace() {
-            // make sure each pattern is sorted
-            let mut pattern: Vec<char> = pattern.chars().collect();
-            pattern.sort_unstable();
-            observed
-                .entry(pattern.len())
-                .or_insert_with(Vec::new)
-                .push(pattern.into_iter().collect());
+        number += 1;
+        let (observed, shown) = line
+            .split_once('|')
+            .ok_or(DecodeError::MissingSeparator { line: number })?;
+        let patterns = |patterns: &str| {
+            patterns
+                .split_whitespace()
+                .map(|pattern| {
+                    mask(pattern, display.segments).ok_or_else(|| DecodeError::InvalidPattern {
+                        line: number,
+                        pattern: pattern.to_owned(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let mut observed = patterns(observed)?;
+        observed.sort_unstable();
+        observed.dedup();
+
+        let (decoded, _) = display.decode(number, &observed, &patterns(shown)?)?;
+        if options.display.is_some() {
+            println!("Line {} shows {}", number, decoded);
         }
 
-        // decode the easy ones first
-        let mut decoder = HashMap::new();
+        // count the symbols, which are easy to tell by their number of segments, and add
+        // up the shown numbers
+        unique += decoded.chars().filter(|&s| display.is_unique(s)).count() as u64;
+        if !decoded.is_empty() && decoded.chars().all(|s| s.is_ascii_digit()) {
+            let reading: Option<u64> = decoded.parse().ok();
+            sum = reading
+                .and_then(|reading| sum.checked_add(reading))
+                .context(Overflow { line: number })?;
+        }
+    }
 
-        // One (only pattern with 2 elements)
-        decoder.insert(observed[&2][0].clone(), 1);
+    Ok([unique, sum])
+}
 
-        // Four (only pattern with 4 elements)
-        decoder.insert(observed[&4][0].clone(), 4);
+/// Encode a line of the puzzle, which shows the requested symbols on a scrambled display
+pub fn scramble(options: &ScrambleOptions) -> Result<String, Box<dyn Error>> {
+    let display = match &options.display.display {
+        Some(path) => Display::parse(&fs::read_to_string(path)?)?,
+        None => Display::seven_segment(),
+    };
 
-        // Seven (only pattern with 3 elements)
-        decoder.insert(observed[&3][0].clone(), 7);
+    let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+    let wires = match &options.wires {
+        Some(wires) => display.parse_wires(wires)?,
+        None => display.random_wires(&mut rng),
+    };
+    Ok(display.encode(&mut rng, &wires, &options.symbols)?)
+}
 
-        // Eight (only pattern with 7 elements)
-        decoder.insert(observed[&7][0].clone(), 8);
+/// Generate `size` displays, each with its own scrambled wiring
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let display = Display::seven_segment();
+    for _ in 0..options.size {
+        // scramble the wires of the display and show four random digits
+        let wires = display.random_wires(rng);
+        let shown: String = (0..4)
+            .map(|_| char::from_digit(rng.gen_range(0..10), 10).unwrap())
+            .collect();
+        let line = display.encode(rng, &wires, &shown).unwrap();
+        writeln!(output, "{}", line)?;
+    }
 
-        // Three (only pattern with 5 elements, which contains the one)
-        let three = observed[&5]
-            .iter()
-            .find(|p| contains_pattern(p, &observed[&2][0]))
-            .unwrap();
-        decoder.insert(three.to_owned(), 3);
+    Ok(())
+}
 
-        // Six (only pattern with 6 elements, which does not contain the seven)
-        let six = observed[&6]
-            .iter()
-            .find(|p| !contains_pattern(p, &observed[&3][0]))
-            .unwrap();
-        decoder.insert(six.to_owned(), 6);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+    use proptest::prelude::*;
 
-        // Nine (only pattern with 6 elements, which is not the six and contains the three)
-        let nine = observed[&6]
-            .iter()
-            .filter(|&p| p != six)
-            .find(|p| contains_pattern(p, three))
-            .unwrap();
-        decoder.insert(nine.to_owned(), 9);
+    const EXAMPLE: &str = "\
+be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
+edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc
+fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg
+fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb
+aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea
+fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb
+dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe
+bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
+egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
+gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
+";
 
-        // Zero (only pattern with 6 elements, which is neither the six nor the nine)
-        let zero = observed[&6]
-            .iter()
-            .find(|&p| p != six && p != nine)
-            .unwrap();
-        decoder.insert(zero.to_owned(), 0);
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines(EXAMPLE), &Options::default()).await.unwrap(),
+            [26, 61229]
+        );
+    }
 
-        // Two (only pattern with 5 elements, which has two common elements with four)
-        let two = observed[&5]
-            .iter()
-            .filter(|&p| p != three)
-            .find(|p| common_character_count(p, &observed[&4][0]) == 2)
-            .unwrap();
-        decoder.insert(two.to_owned(), 2);
+    #[tokio::test]
+    async fn single_entry() {
+        let input = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | \
+                     cdfeb fcadb cdfeb cdbaf";
+        assert_eq!(
+            execute(lines(input), &Options::default()).await.unwrap(),
+            [0, 5353]
+        );
+    }
 
-        // Five (only pattern with 5 elements, which neither the three nor the two)
-        let five = observed[&5]
-            .iter()
-            .find(|&p| p != three && p != two)
-            .unwrap();
-        decoder.insert(five.to_owned(), 5);
-
-        // take the input pattern and make sure each entry is sorted
-        let input_pattern = split[1].split_ascii_whitespace().map(|p| {
-            let mut p: Vec<char> = p.chars().collect();
-            p.sort_unstable();
-            p.into_iter().collect::<String>()
-        });
+    #[tokio::test]
+    async fn undecodable_lines() {
+        let error = |input| async move {
+            execute(lines(input), &Options::default())
+                .await
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("ab | ab\nabcdef abcdeg\n").await,
+            "Missing separator of the shown patterns in line 2"
+        );
+        assert_eq!(error("ab | ax").await, "Invalid pattern \"ax\" in line 1");
+        assert_eq!(
+            error("ab abcd abce | ab").await,
+            "No wiring of the display shows the patterns of line 1"
+        );
+        assert_eq!(error("| abcde").await, "The patterns of line 1 show 2 or 3");
+        assert_eq!(
+            error(&format!("| {}", ["abcdefg"; 20].join(" "))).await,
+            "The sum of the readings up to line 1 does not fit into 64 bits"
+        );
+        assert_eq!(
+            execute(lines("| ab abdf abe abcdefg"), &Options::default())
+                .await
+                .unwrap(),
+            [4, 1478]
+        );
+    }
+
+    #[test]
+    fn sixteen_segment_display() {
+        // some letters of a sixteen segment display with split horizontal bars a, b, e, f
+        // and i, j, the outer verticals c, d, g, h and the inner segments k to p
+        let display = Display::parse(
+            "\
+A abcdghij
+C abefgh
+E abefghi
+H cdghij
+I abeflo
+K ghimp
+L efgh
+O abcdefgh
+T ablo
+",
+        )
+        .unwrap();
+        assert_eq!(display.segments, 16);
+
+        // reverse the wires, observe all letters and show a word
+        let scrambled = |symbols: &str| -> Vec<u32> {
+            symbols
+                .chars()
+                .map(|symbol| {
+                    let (_, glyph) = display.glyphs.iter().find(|(s, _)| *s == symbol).unwrap();
+                    glyph.reverse_bits() >> 16
+                })
+                .collect()
+        };
+        let mut observed = scrambled("ACEHIKLOT");
+        observed.sort_unstable();
+        assert_eq!(
+            display.decode(1, &observed, &scrambled("HELLO")).unwrap().0,
+            "HELLO"
+        );
+        assert_eq!(
+            display
+                .decode(1, &[], &scrambled("I"))
+                .unwrap_err()
+                .to_string(),
+            "The patterns of line 1 show C or H"
+        );
+    }
+
+    #[test]
+    fn scramble_example_wiring() {
+        let options = ScrambleOptions {
+            symbols: "5353".to_owned(),
+            wires: Some("deafgbc".to_owned()),
+            seed: 0,
+            display: Options::default(),
+        };
+        let line = scramble(&options).unwrap();
+        let sorted = |patterns: &str| -> Vec<String> {
+            patterns
+                .split_whitespace()
+                .map(|pattern| {
+                    let mut pattern: Vec<_> = pattern.chars().collect();
+                    pattern.sort_unstable();
+                    pattern.into_iter().collect()
+                })
+                .collect()
+        };
 
-        // put the length of each pattern in a hist
-        for (i, pattern) in input_pattern.rev().enumerate() {
-            // decode digit
-            let digit = *decoder.get(&pattern).unwrap();
+        // the wiring of the single entry of the puzzle description
+        let (observed, shown) = line.split_once(" | ").unwrap();
+        let mut observed = sorted(observed);
+        observed.sort_unstable();
+        let mut expected = sorted("acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab");
+        expected.sort_unstable();
+        assert_eq!(observed, expected);
+        assert_eq!(sorted(shown), sorted("cdfeb fcadb cdfeb cdbaf"));
 
-            // update histogram and total sum
-            sum += digit * 10u64.pow(i as u32);
-            hist[digit as usize] += 1;
+        let invalid = |wires: &str| ScrambleOptions {
+            wires: Some(wires.to_owned()),
+            ..options.clone()
+        };
+        assert!(scramble(&invalid("deafgb")).is_err());
+        assert!(scramble(&invalid("deafgbb")).is_err());
+        assert!(scramble(&invalid("deafgbh")).is_err());
+        assert!(scramble(&ScrambleOptions {
+            symbols: "12a".to_owned(),
+            ..options
+        })
+        .is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn decode_recovers_scrambled(seed: u64, digits in "[0-9]{1,8}") {
+            let display = Display::seven_segment();
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let wires = display.random_wires(&mut rng);
+            let line = display.encode(&mut rng, &wires, &digits).unwrap();
+
+            let (observed, shown) = line.split_once(" | ").unwrap();
+            let patterns = |patterns: &str| -> Vec<u32> {
+                patterns.split_whitespace().map(|p| mask(p, 7).unwrap()).collect()
+            };
+            let (decoded, wiring) = display.decode(1, &patterns(observed), &patterns(shown)).unwrap();
+            prop_assert_eq!(decoded, digits);
+
+            // the decoded wiring connects each wire back to its segment
+            for (segment, wire) in wires.into_iter().enumerate() {
+                prop_assert_eq!(wiring[(wire as u8 - b'a') as usize], segment);
+            }
         }
     }
 
-    Ok([hist[1] + hist[4] + hist[7] + hist[8], sum])
+    #[test]
+    fn invalid_display() {
+        assert!(Display::parse("1 ab\n2 ab").is_err());
+        assert!(Display::parse("1 ab\n1 abc").is_err());
+        assert!(Display::parse("10 ab").is_err());
+        assert!(Display::parse("1 aB").is_err());
+    }
 }
@@ -1,10 +1,207 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
 use ndarray::prelude::*;
-use std::{collections::HashMap, error::Error};
+use rand::Rng;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Deref,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+// Options of the heightmap. Not a doc comment, as it is flattened into the options of
+// the command line.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    /// Draw the heightmap of day 09 in the terminal with each basin in its own colour
+    /// and the low points highlighted, and list the largest basins
+    #[structopt(long)]
+    pub basins_ansi: bool,
+
+    /// Save the heightmap of day 09 as PPM image with each basin in its own colour,
+    /// which gets darker uphill, the low points in white and the ridges in black
+    #[structopt(long, parse(from_os_str))]
+    pub basins_image: Option<PathBuf>,
+}
+
+/// Find the low points of the heightmap, which are lower than all adjacent locations
+fn low_points(map: &Array2<u32>) -> HashMap<(usize, usize), u32> {
+    let mut lows = HashMap::new();
+    for ((i, j), v) in map.indexed_iter() {
+        if i > 0 && map[[i - 1, j]] <= *v {
+            continue;
+        }
+        if i < map.shape()[0] - 1 && map[[i + 1, j]] <= *v {
+            continue;
+        }
+        if j > 0 && map[[i, j - 1]] <= *v {
+            continue;
+        }
+        if j < map.shape()[1] - 1 && map[[i, j + 1]] <= *v {
+            continue;
+        }
+        lows.insert((i, j), *v);
+    }
+
+    lows
+}
+
+/// A basin, which is a connected area of locations lower than 9
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Basin {
+    /// The number of locations in the basin
+    size: u32,
+    /// The lowest location of the basin, the first in reading order on ties
+    low: (usize, usize),
+    /// The height difference between the highest and the lowest location of the basin
+    depth: u32,
+}
+
+/// Label the basins of the heightmap by flooding each area enclosed by locations of
+/// height 9. Returns the label of each location, which is 0 for height 9 and the index
+/// of its basin plus one otherwise, and the basins.
+fn basins(map: &Array2<u32>) -> (Array2<u32>, Vec<Basin>) {
+    let (rows, columns) = map.dim();
+    let mut labels = Array2::zeros((rows, columns));
+    let mut basins = Vec::new();
+    let mut queue = VecDeque::new();
+    for (start, _) in map.indexed_iter().filter(|(_, &v)| v != 9) {
+        if labels[start] != 0 {
+            continue;
+        }
+
+        // flood the basin breadth first
+        let label = basins.len() as u32 + 1;
+        let mut basin = Basin {
+            size: 0,
+            low: start,
+            depth: 0,
+        };
+        let mut highest = map[start];
+        labels[start] = label;
+        queue.push_back(start);
+        while let Some((i, j)) = queue.pop_front() {
+            let height = map[[i, j]];
+            basin.size += 1;
+            highest = highest.max(height);
+            if (height, (i, j)) < (map[basin.low], basin.low) {
+                basin.low = (i, j);
+            }
+
+            let neighbours = [
+                (i.wrapping_sub(1), j),
+                (i + 1, j),
+                (i, j.wrapping_sub(1)),
+                (i, j + 1),
+            ];
+            for n in neighbours {
+                if map.get(n).is_some_and(|&v| v != 9) && labels[n] == 0 {
+                    labels[n] = label;
+                    queue.push_back(n);
+                }
+            }
+        }
+        basin.depth = highest - map[basin.low];
+        basins.push(basin);
+    }
+
+    (labels, basins)
+}
+
+/// Order the basins by decreasing size, ties in the order of their labels. Returns the
+/// rank of each basin, which is 0 for the largest one.
+fn ranks(basins: &[Basin]) -> Vec<usize> {
+    let mut order: Vec<_> = (0..basins.len()).collect();
+    order.sort_by_key(|&basin| Reverse(basins[basin].size));
+    let mut ranks = vec![0; basins.len()];
+    for (rank, basin) in order.into_iter().enumerate() {
+        ranks[basin] = rank;
+    }
+    ranks
+}
+
+/// The colour of the basin with the given rank. The hues are spread by the golden
+/// angle, so that basins of close ranks get clearly different colours.
+fn colour(rank: usize) -> [u8; 3] {
+    let hue = (rank as f64 * 0.618_033_988_75).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [r, g, b].map(|c| (55.0 + 200.0 * c) as u8)
+}
+
+/// The colours of a drawing of the heightmap, which highlights the basins
+struct Drawing<'a> {
+    map: &'a Array2<u32>,
+    labels: &'a Array2<u32>,
+    ranks: &'a [usize],
+    lows: &'a HashMap<(usize, usize), u32>,
+}
+
+impl Drawing<'_> {
+    /// The colour of the basin of a location, if it is not on a ridge
+    fn basin(&self, index: (usize, usize)) -> Option<[u8; 3]> {
+        match self.labels[index] {
+            0 => None,
+            label => Some(colour(self.ranks[label as usize - 1])),
+        }
+    }
+
+    /// Write the heights with the colour of their basin as background in ANSI escape
+    /// codes. Low points are bold white and ridges are dimmed.
+    fn write_ansi(&self, output: &mut impl Write) -> io::Result<()> {
+        for (i, heights) in self.map.rows().into_iter().enumerate() {
+            for (j, height) in heights.iter().enumerate() {
+                match self.basin((i, j)) {
+                    None => write!(output, "\x1b[2m{}", height)?,
+                    Some([r, g, b]) => {
+                        let text = if self.lows.contains_key(&(i, j)) {
+                            "1;97"
+                        } else {
+                            "30"
+                        };
+                        write!(output, "\x1b[{};48;2;{};{};{}m{}", text, r, g, b, height)?;
+                    }
+                }
+                write!(output, "\x1b[0m")?;
+            }
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+
+    /// Write a PPM image with one pixel per location. The colour of a basin gets darker
+    /// uphill, low points are white and ridges are black.
+    fn write_image(&self, output: &mut impl Write) -> io::Result<()> {
+        let (rows, columns) = self.map.dim();
+        writeln!(output, "P6\n{} {}\n255", columns, rows)?;
+        let mut pixels = Vec::with_capacity(rows * columns * 3);
+        for (index, &height) in self.map.indexed_iter() {
+            let pixel = match self.basin(index) {
+                None => [0; 3],
+                Some(_) if self.lows.contains_key(&index) => [255; 3],
+                Some(colour) => colour.map(|c| (c as u32 * (12 - height) / 12) as u8),
+            };
+            pixels.extend(pixel);
+        }
+        output.write_all(&pixels)
+    }
+}
 
 /// Executes the exercise of day 9
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
@@ -28,61 +225,318 @@ pub async fn execute<E: Error + 'static>(
     let map = map.unwrap();
 
     // Find the low points
-    let mut lows = HashMap::new();
-    for ((i, j), v) in map.indexed_iter() {
-        if i > 0 && map[[i - 1, j]] <= *v {
-            continue;
-        }
-        if i < map.shape()[0] - 1 && map[[i + 1, j]] <= *v {
-            continue;
-        }
-        if j > 0 && map[[i, j - 1]] <= *v {
-            continue;
+    let lows = low_points(&map);
+    let risk: u32 = lows.iter().map(|((_, _), v)| 1 + v).sum();
+
+    // rank the basins by its size
+    let (labels, basins) = basins(&map);
+    let ranks = ranks(&basins);
+    let mut largest: Vec<_> = basins.iter().zip(&ranks).collect();
+    largest.sort_unstable_by_key(|&(_, &rank)| rank);
+    largest.truncate(3);
+
+    // draw the basins
+    let drawing = Drawing {
+        map: &map,
+        labels: &labels,
+        ranks: &ranks,
+        lows: &lows,
+    };
+    if options.basins_ansi {
+        drawing.write_ansi(&mut io::stdout().lock())?;
+        for &(basin, rank) in &largest {
+            println!(
+                "Basin #{}: {} locations, low point at {},{} and {} deep",
+                rank + 1,
+                basin.size,
+                basin.low.0,
+                basin.low.1,
+                basin.depth
+            );
         }
-        if j < map.shape()[1] - 1 && map[[i, j + 1]] <= *v {
-            continue;
+    }
+    if let Some(path) = &options.basins_image {
+        let mut file = BufWriter::new(File::create(path)?);
+        drawing.write_image(&mut file)?;
+        file.flush()?;
+        println!("Saved the basins to {}", path.display());
+    }
+
+    Ok([
+        risk as u64,
+        largest
+            .into_iter()
+            .map(|(basin, _)| basin.size)
+            .product::<u32>() as u64,
+    ])
+}
+
+/// Generate a `size` x `size` heightmap, whose basins are separated by locations of
+/// height 9 and always lead downhill to exactly one low point
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let size = options.size;
+    if size == 0 {
+        return Ok(());
+    }
+
+    // helper to get the adjacent locations of a location
+    let neighbours = |(i, j): (usize, usize)| {
+        [
+            (i.wrapping_sub(1), j),
+            (i + 1, j),
+            (i, j.wrapping_sub(1)),
+            (i, j + 1),
+        ]
+        .into_iter()
+        .filter(move |&(i, j)| i < size && j < size)
+    };
+
+    // scatter some low points, which are never adjacent to each other
+    let mut basins = Array2::from_elem((size, size), None);
+    let mut lows = Vec::new();
+    for _ in 0..(size * size / 20).max(1) {
+        let low = (rng.gen_range(0..size), rng.gen_range(0..size));
+        if basins[low].is_none() && neighbours(low).all(|n| basins[n].is_none()) {
+            basins[low] = Some(lows.len());
+            lows.push(low);
         }
-        lows.insert((i, j), *v);
     }
-    let risk: u32 = lows.iter().map(|((_, _), v)| 1 + v).sum();
 
-    // Find the basin for each point, while ignoring points with height 9
-    let mut basins = HashMap::new();
-    for ((i, j), v) in map.indexed_iter().filter(|(_, &v)| v != 9) {
-        // follow the gradient until a low point has been reached
-        let (mut x, mut y) = (i, j);
-        loop {
-            // exit, if x and y have reached a low point
-            if lows.contains_key(&(x, y)) {
-                *basins.entry((x, y)).or_insert(0u32) += 1;
-                break;
+    // assign each location to the basin of its closest low point
+    let mut queue: VecDeque<_> = lows.iter().copied().collect();
+    while let Some(pos) = queue.pop_front() {
+        for n in neighbours(pos) {
+            if basins[n].is_none() {
+                basins[n] = basins[pos];
+                queue.push_back(n);
             }
+        }
+    }
 
-            // find the direction of steepest decline and move towards it
-            let mut dir = (x, y, *v);
-            if x > 0 && map[[x - 1, y]] < dir.2 {
-                dir = (x - 1, y, map[[x - 1, y]]);
+    // grow the height from each low point, but leave the ridge between two basins at 9
+    let ridge = |pos| neighbours(pos).any(|n| basins[n] != basins[pos]);
+    let mut map = Array2::from_elem((size, size), 9);
+    for low in lows.into_iter().filter(|&low| !ridge(low)) {
+        map[low] = 0;
+        queue.push_back(low);
+    }
+    while let Some(pos) = queue.pop_front() {
+        for n in neighbours(pos) {
+            if map[n] == 9 && map[pos] < 8 && !ridge(n) {
+                map[n] = map[pos] + 1;
+                queue.push_back(n);
             }
-            if x < map.shape()[0] - 1 && map[[x + 1, y]] < dir.2 {
-                dir = (x + 1, y, map[[x + 1, y]]);
+        }
+    }
+
+    for row in map.rows() {
+        let row: String = row.iter().map(|h| h.to_string()).collect();
+        writeln!(output, "{}", row)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Reference, which joins adjacent points below height 9 into sets by union-find
+    fn basin_sizes_union_find(map: &Array2<u32>) -> Vec<u32> {
+        fn root(parents: &mut [usize], mut node: usize) -> usize {
+            while parents[node] != node {
+                parents[node] = parents[parents[node]];
+                node = parents[node];
             }
-            if y > 0 && map[[x, y - 1]] < dir.2 {
-                dir = (x, y - 1, map[[x, y - 1]]);
+            node
+        }
+
+        let (rows, columns) = map.dim();
+        let mut parents: Vec<_> = (0..rows * columns).collect();
+        for ((i, j), _) in map.indexed_iter().filter(|(_, &v)| v != 9) {
+            for (ni, nj) in [(i + 1, j), (i, j + 1)] {
+                if map.get((ni, nj)).is_some_and(|&n| n != 9) {
+                    let (a, b) = (
+                        root(&mut parents, i * columns + j),
+                        root(&mut parents, ni * columns + nj),
+                    );
+                    parents[a] = b;
+                }
             }
-            if y < map.shape()[1] - 1 && map[[x, y + 1]] < dir.2 {
-                dir = (x, y + 1, map[[x, y + 1]]);
+        }
+
+        let mut sizes = HashMap::new();
+        for ((i, j), _) in map.indexed_iter().filter(|(_, &v)| v != 9) {
+            *sizes
+                .entry(root(&mut parents, i * columns + j))
+                .or_insert(0) += 1;
+        }
+        sizes.into_values().collect()
+    }
+
+    const EXAMPLE: &str = "\
+2199943210
+3987894921
+9856789892
+8767896789
+9899965678
+";
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines(EXAMPLE), &Options::default()).await.unwrap(),
+            [15, 1134]
+        );
+    }
+
+    #[test]
+    fn example_basins() {
+        let rows: Vec<Vec<u32>> = EXAMPLE
+            .lines()
+            .map(|line| line.chars().map(|c| c.to_digit(10).unwrap()).collect())
+            .collect();
+        let map = Array2::from_shape_fn((rows.len(), rows[0].len()), |(i, j)| rows[i][j]);
+        let (labels, basins) = basins(&map);
+
+        let basin = |size, low, depth| Basin { size, low, depth };
+        assert_eq!(
+            basins,
+            [
+                basin(3, (0, 1), 2),
+                basin(9, (0, 9), 4),
+                basin(14, (2, 2), 3),
+                basin(9, (4, 6), 3),
+            ]
+        );
+        assert_eq!(labels.row(0), aview1(&[1, 1, 0, 0, 0, 2, 2, 2, 2, 2]));
+        assert_eq!(labels.row(4), aview1(&[0, 3, 0, 0, 0, 4, 4, 4, 4, 4]));
+    }
+
+    #[test]
+    fn drawing() {
+        let map = arr2(&[[0, 9], [1, 9]]);
+        let (labels, basins) = basins(&map);
+        let ranks = ranks(&basins);
+        let drawing = Drawing {
+            map: &map,
+            labels: &labels,
+            ranks: &ranks,
+            lows: &low_points(&map),
+        };
+
+        let [r, g, b] = colour(0);
+        let mut text = Vec::new();
+        drawing.write_ansi(&mut text).unwrap();
+        let low = format!("\x1b[1;97;48;2;{};{};{}m0\x1b[0m", r, g, b);
+        let uphill = format!("\x1b[30;48;2;{};{};{}m1\x1b[0m", r, g, b);
+        let ridge = "\x1b[2m9\x1b[0m";
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            format!("{}{}\n{}{}\n", low, ridge, uphill, ridge)
+        );
+
+        let mut image = Vec::new();
+        drawing.write_image(&mut image).unwrap();
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend([255, 255, 255, 0, 0, 0]);
+        expected.extend([r, g, b].map(|c| (c as u32 * 11 / 12) as u8));
+        expected.extend([0, 0, 0]);
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn ranks_and_colours() {
+        let basin = |size| Basin {
+            size,
+            low: (0, 0),
+            depth: 0,
+        };
+        assert_eq!(
+            ranks(&[basin(3), basin(9), basin(14), basin(9)]),
+            [3, 1, 0, 2]
+        );
+
+        // the first colours are all distinguishable
+        let colours: Vec<_> = (0..8).map(colour).collect();
+        for (i, a) in colours.iter().enumerate() {
+            for b in &colours[i + 1..] {
+                let distance: u32 = a.iter().zip(b).map(|(&a, &b)| a.abs_diff(b) as u32).sum();
+                assert!(distance > 60, "{:?} and {:?} are too close", a, b);
             }
-            x = dir.0;
-            y = dir.1;
         }
     }
 
-    // sort the basins by its size
-    let mut basin_size: Vec<_> = basins.iter().map(|(_, size)| *size).collect();
-    basin_size.sort_unstable();
+    #[tokio::test]
+    async fn plateaus() {
+        // equal neighbours have no downhill direction, but still belong to a basin
+        let input = "\
+55955
+55959
+99999
+12399
+";
+        assert_eq!(
+            execute(lines(input), &Options::default()).await.unwrap(),
+            [2, 4 * 3 * 3]
+        );
+        assert_eq!(
+            execute(lines("777\n"), &Options::default()).await.unwrap(),
+            [0, 3]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn basin_sizes_match_reference(seed: u64, size in 1usize..20) {
+            let options = GeneratorOptions { seed, size, scale: None };
+            let mut input = Vec::new();
+            generate(&mut ChaCha8Rng::seed_from_u64(seed), &options, &mut input).unwrap();
+            let digits: Vec<u32> = input
+                .iter()
+                .filter(|c| c.is_ascii_digit())
+                .map(|c| (c - b'0') as u32)
+                .collect();
+            let map = Array2::from_shape_vec((size, size), digits).unwrap();
 
-    Ok([
-        risk as u64,
-        basin_size.into_iter().rev().take(3).product::<u32>() as u64,
-    ])
+            let mut fast: Vec<_> = basins(&map).1.into_iter().map(|basin| basin.size).collect();
+            let mut reference = basin_sizes_union_find(&map);
+            fast.sort_unstable();
+            reference.sort_unstable();
+            prop_assert_eq!(fast, reference);
+        }
+
+        #[test]
+        fn basins_of_arbitrary_maps(
+            map in (1usize..12, 1usize..12).prop_flat_map(|(rows, columns)| {
+                prop::collection::vec(prop_oneof![0u32..9, Just(9)], rows * columns)
+                    .prop_map(move |v| Array2::from_shape_vec((rows, columns), v).unwrap())
+            })
+        ) {
+            let (labels, basins) = basins(&map);
+            let mut sizes: Vec<_> = basins.iter().map(|basin| basin.size).collect();
+            let mut reference = basin_sizes_union_find(&map);
+            sizes.sort_unstable();
+            reference.sort_unstable();
+            prop_assert_eq!(sizes, reference);
+
+            // each basin holds its low point, and its heights stay within its depth
+            for (label, basin) in basins.iter().enumerate() {
+                prop_assert_eq!(labels[basin.low] as usize, label + 1);
+            }
+            for (index, &label) in labels.indexed_iter().filter(|(_, &label)| label != 0) {
+                let basin = &basins[label as usize - 1];
+                prop_assert!(map[index] >= map[basin.low]);
+                prop_assert!(map[index] - map[basin.low] <= basin.depth);
+            }
+        }
+    }
 }
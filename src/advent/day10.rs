@@ -1,87 +1,400 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
-use std::error::Error;
-
-/// Helper to calculate the syntax error score for a character
-#[inline]
-fn syntax_error_score(char: char) -> u64 {
-    match char {
-        ')' => 3,
-        ']' => 57,
-        '}' => 1197,
-        '>' => 25137,
-        _ => 0,
+use rand::Rng;
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Write},
+    ops::Deref,
+    str::FromStr,
+};
+use structopt::StructOpt;
+
+// Options of the bracket linter. Not a doc comment, as it is flattened into the options
+// of the command line.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    /// Lint the lines of day 10 with these bracket pairs instead of (), [], {} and <>.
+    /// Each pair may be followed by its syntax error score, like `<>:25137`, which
+    /// defaults to the score of the puzzle, or 0 for other brackets.
+    #[structopt(long)]
+    pub brackets: Vec<Pair>,
+
+    /// Print the report of the linter of day 10 for each line
+    #[structopt(long)]
+    pub brackets_lint: bool,
+}
+
+/// A pair of brackets, which open and close a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pair {
+    open: char,
+    close: char,
+    /// The syntax error score of a corrupted chunk, which ends with the closing bracket
+    score: u64,
+}
+
+impl Pair {
+    /// Create a pair with the syntax error score of the puzzle
+    fn new(open: char, close: char) -> Self {
+        let score = match close {
+            ')' => 3,
+            ']' => 57,
+            '}' => 1197,
+            '>' => 25137,
+            _ => 0,
+        };
+        Self { open, close, score }
     }
 }
 
-/// Helper to calculate the autocompletion score for a character
-#[inline]
-fn autocompletion_score(char: char) -> u64 {
-    match char {
-        '(' => 1,
-        '[' => 2,
-        '{' => 3,
-        '<' => 4,
-        _ => 0,
+impl FromStr for Pair {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (brackets, score) = match s.split_once(':') {
+            Some((brackets, score)) => (brackets, Some(score)),
+            None => (s, None),
+        };
+        let mut chars = brackets.chars();
+        let pair = match (chars.next(), chars.next(), chars.next()) {
+            (Some(open), Some(close), None) if open != close => Pair::new(open, close),
+            _ => return Err(format!("Invalid bracket pair {:?}", s)),
+        };
+        match score.map(str::parse) {
+            None => Ok(pair),
+            Some(Ok(score)) => Ok(Self { score, ..pair }),
+            Some(Err(_)) => Err(format!("Invalid syntax error score in {:?}", s)),
+        }
     }
 }
+
+/// The verdict of the linter on a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Report {
+    /// All chunks are closed
+    Valid,
+    /// A chunk is closed with the wrong bracket, or a bracket closes no chunk at all
+    Corrupted {
+        /// The column of the wrong bracket, starting at 1
+        column: usize,
+        /// The bracket, which would have closed the innermost open chunk
+        expected: Option<char>,
+        found: char,
+    },
+    /// Some chunks are still open at the end of the line
+    Incomplete {
+        /// The brackets to close all open chunks
+        completion: String,
+    },
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Valid => write!(f, "valid"),
+            Self::Corrupted {
+                column,
+                expected: Some(expected),
+                found,
+            } => write!(
+                f,
+                "corrupted at column {}, expected {} but found {}",
+                column, expected, found
+            ),
+            Self::Corrupted {
+                column,
+                expected: None,
+                found,
+            } => write!(
+                f,
+                "corrupted at column {}, found {} without an open chunk",
+                column, found
+            ),
+            Self::Incomplete { completion } => {
+                write!(f, "incomplete, complete with {}", completion)
+            }
+        }
+    }
+}
+
+/// The linter, which checks the chunks of lines with a configurable set of brackets
+#[derive(Debug, Clone)]
+struct Linter {
+    pairs: Vec<Pair>,
+}
+
+impl Default for Linter {
+    /// The brackets of the puzzle
+    fn default() -> Self {
+        Self::new(vec![
+            Pair::new('(', ')'),
+            Pair::new('[', ']'),
+            Pair::new('{', '}'),
+            Pair::new('<', '>'),
+        ])
+    }
+}
+
+impl Linter {
+    fn new(pairs: Vec<Pair>) -> Self {
+        Self { pairs }
+    }
+
+    /// Check the chunks of a line. Characters, which are no brackets at all, corrupt the
+    /// line just like wrong closing brackets.
+    fn lint(&self, line: &str) -> Report {
+        let mut chunks = Vec::new();
+        for (column, found) in line.chars().enumerate() {
+            if let Some(pair) = self.pairs.iter().find(|pair| pair.open == found) {
+                chunks.push(pair);
+            } else {
+                match chunks.pop() {
+                    Some(pair) if pair.close == found => {}
+                    chunk => {
+                        return Report::Corrupted {
+                            column: column + 1,
+                            expected: chunk.map(|pair| pair.close),
+                            found,
+                        }
+                    }
+                }
+            }
+        }
+
+        if chunks.is_empty() {
+            Report::Valid
+        } else {
+            let completion = chunks.into_iter().rev().map(|pair| pair.close).collect();
+            Report::Incomplete { completion }
+        }
+    }
+
+    /// The syntax error score of a report, which is only positive for corrupted lines
+    fn syntax_error_score(&self, report: &Report) -> u64 {
+        match report {
+            Report::Corrupted { found, .. } => self
+                .pairs
+                .iter()
+                .find(|pair| pair.close == *found)
+                .map_or(0, |pair| pair.score),
+            _ => 0,
+        }
+    }
+
+    /// The autocompletion score of an incomplete line. Each closing bracket is a digit
+    /// of its position in the pairs plus one, in the base of the number of pairs plus
+    /// one. Returns None, if the score overflows.
+    fn autocompletion_score(&self, report: &Report) -> Option<u64> {
+        let completion = match report {
+            Report::Incomplete { completion } => completion,
+            _ => return Some(0),
+        };
+        let base = self.pairs.len() as u64 + 1;
+        completion.chars().try_fold(0u64, |score, close| {
+            let digit = self.pairs.iter().position(|pair| pair.close == close)? as u64 + 1;
+            score.checked_mul(base)?.checked_add(digit)
+        })
+    }
+}
+
 /// Executes the exercise of day 10
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
+    let linter = if options.brackets.is_empty() {
+        Linter::default()
+    } else {
+        Linter::new(options.brackets.clone())
+    };
 
-    // read in lines and disregard corrupted ones
+    // lint each line and score the corrupted and the incomplete ones
     let mut error_score = 0;
     let mut completion_scores = Vec::new();
-    'line: while let Some(line) = input.try_next().await? {
-        // verify line chunks
+    let mut number = 0;
+    while let Some(line) = input.try_next().await? {
+        number += 1;
+        let report = linter.lint(&line);
+        if options.brackets_lint {
+            println!("Line {}: {}", number, report);
+        }
+
+        error_score += linter.syntax_error_score(&report);
+        if let Report::Incomplete { .. } = report {
+            completion_scores.push(linter.autocompletion_score(&report).ok_or_else(|| {
+                format!("The autocompletion score of line {} is too large", number)
+            })?);
+        }
+    }
+
+    // take the middle score of the incomplete lines
+    completion_scores.sort_unstable();
+    let completion_score = completion_scores
+        .get(completion_scores.len() / 2)
+        .copied()
+        .ok_or("There are no incomplete lines")?;
+
+    Ok([error_score, completion_score])
+}
+
+/// Generate `size` lines of up to `scale` chunk characters, which are either corrupted
+/// or incomplete. There is always an odd number of incomplete lines.
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    const PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+    // limit the nesting depth to keep the autocompletion score within an u64
+    const MAX_DEPTH: usize = 20;
+    let length = options.scale.unwrap_or(100).max(2) as usize;
+
+    // decide which lines are incomplete upfront to get an odd number of them
+    let mut incomplete: Vec<bool> = (0..options.size).map(|_| rng.gen()).collect();
+    if incomplete.iter().filter(|i| **i).count() % 2 == 0 {
+        if let Some(last) = incomplete.last_mut() {
+            *last = !*last;
+        }
+    }
+
+    for incomplete in incomplete {
+        // open and close chunks at random
+        let mut line = String::new();
         let mut chunks = Vec::new();
-        for char in line.chars() {
-            match char {
-                // open a new chunk
-                '(' | '{' | '[' | '<' => chunks.push(char),
-                // close a chunk
-                _ => {
-                    let chunk = chunks.pop();
-                    match chunk {
-                        Some('(') => {
-                            if char != ')' {
-                                error_score += syntax_error_score(char);
-                                continue 'line;
-                            }
-                        }
-                        Some('{') => {
-                            if char != '}' {
-                                error_score += syntax_error_score(char);
-                                continue 'line;
-                            }
-                        }
-                        Some('[') => {
-                            if char != ']' {
-                                error_score += syntax_error_score(char);
-                                continue 'line;
-                            }
-                        }
-                        Some('<') => {
-                            if char != '>' {
-                                error_score += syntax_error_score(char);
-                                continue 'line;
-                            }
-                        }
-                        _ => panic!(),
-                    }
-                }
+        for _ in 0..rng.gen_range(1..length) {
+            if chunks.len() < MAX_DEPTH && (chunks.is_empty() || rng.gen()) {
+                let (open, close) = PAIRS[rng.gen_range(0..PAIRS.len())];
+                chunks.push(close);
+                line.push(open);
+            } else {
+                line.push(chunks.pop().unwrap());
             }
         }
-        completion_scores.push(
-            chunks
-                .into_iter()
-                .rev()
-                .fold(0, |acc, c| acc * 5 + autocompletion_score(c)),
+
+        // make sure at least one chunk is left open
+        if chunks.is_empty() {
+            let (open, close) = PAIRS[rng.gen_range(0..PAIRS.len())];
+            chunks.push(close);
+            line.push(open);
+        }
+
+        // close the last chunk with a wrong character, followed by some garbage
+        if !incomplete {
+            let expected = chunks.pop().unwrap();
+            let wrong: Vec<_> = PAIRS.iter().filter(|p| p.1 != expected).collect();
+            line.push(wrong[rng.gen_range(0..wrong.len())].1);
+            for _ in 0..rng.gen_range(0..length / 4 + 1) {
+                let pair = PAIRS[rng.gen_range(0..PAIRS.len())];
+                line.push(if rng.gen() { pair.0 } else { pair.1 });
+            }
+        }
+        writeln!(output, "{}", line)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+
+    const EXAMPLE: &str = "\
+[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]
+[{[{({}]{}}([{[{{{}}([]
+{<[[]]>}<{[{[{[]{()[[[]
+[<(<(<(<{}))><([]([]()
+<{([([[(<>()){}]>(<<{{
+<{([{{}}[<[[[<>{}]]]>[]]
+";
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines(EXAMPLE), &Options::default()).await.unwrap(),
+            [26397, 288957]
         );
     }
-    completion_scores.sort_unstable();
-    let completion_score = completion_scores[completion_scores.len() / 2];
 
-    Ok([error_score, completion_score])
+    #[tokio::test]
+    async fn extra_closing_bracket() {
+        // the surplus bracket corrupts the line, the other one is incomplete
+        let input = "(<>)]\n[<>\n";
+        assert_eq!(
+            execute(lines(input), &Options::default()).await.unwrap(),
+            [57, 2]
+        );
+    }
+
+    #[test]
+    fn example_reports() {
+        let linter = Linter::default();
+        let reports: Vec<_> = EXAMPLE.lines().map(|line| linter.lint(line)).collect();
+        assert_eq!(
+            reports[0],
+            Report::Incomplete {
+                completion: "}}]])})]".to_owned()
+            }
+        );
+        assert_eq!(linter.autocompletion_score(&reports[0]), Some(288957));
+        assert_eq!(
+            reports[2],
+            Report::Corrupted {
+                column: 13,
+                expected: Some(']'),
+                found: '}'
+            }
+        );
+        assert_eq!(linter.syntax_error_score(&reports[2]), 1197);
+        assert_eq!(
+            reports[2].to_string(),
+            "corrupted at column 13, expected ] but found }"
+        );
+        assert_eq!(linter.lint("{<>[]}"), Report::Valid);
+        assert_eq!(
+            linter.lint("()]").to_string(),
+            "corrupted at column 3, found ] without an open chunk"
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_brackets() {
+        let options = Options {
+            brackets: vec!["()".parse().unwrap(), "«»:10".parse().unwrap()],
+            brackets_lint: false,
+        };
+        // the base of the autocompletion score is the number of pairs plus one
+        let input = "(«)\n((«»\n«(\n«(«\n(»\n[]\n";
+        assert_eq!(
+            execute(lines(input), &options).await.unwrap(),
+            [3 + 10, 3 + 2]
+        );
+
+        assert!("(".parse::<Pair>().is_err());
+        assert!("((".parse::<Pair>().is_err());
+        assert!("()]".parse::<Pair>().is_err());
+        assert!("():x".parse::<Pair>().is_err());
+        assert_eq!(
+            "[]".parse::<Pair>().unwrap(),
+            Pair {
+                open: '[',
+                close: ']',
+                score: 57
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn no_incomplete_lines() {
+        let error = execute(lines("()\n(]\n"), &Options::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "There are no incomplete lines");
+    }
 }
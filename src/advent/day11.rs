@@ -1,10 +1,370 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
 use ndarray::prelude::*;
-use std::error::Error;
+use rand::Rng;
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Deref,
+    path::PathBuf,
+    time::Duration,
+};
+use structopt::StructOpt;
+use strum::{Display, EnumString};
+
+// Options of the octopus simulation. Not a doc comment, as it is flattened into the
+// options of the command line.
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// Octopuses of day 11 flash, once their energy level exceeds this threshold
+    #[structopt(long, default_value = "9")]
+    pub octopus_threshold: u32,
+
+    /// The number of adjacent octopuses of day 11, which gain energy from a flash:
+    /// 8 including the diagonals, or 4 without them
+    #[structopt(long, default_value = "8")]
+    pub octopus_neighbourhood: Neighbourhood,
+
+    /// Wrap the edges of the map of day 11 around, so that octopuses on opposite edges
+    /// are adjacent
+    #[structopt(long)]
+    pub octopus_wrap: bool,
+
+    /// Count the flashes of day 11 within this many steps
+    #[structopt(long, default_value = "100")]
+    pub octopus_steps: usize,
+
+    /// Give up waiting for the octopuses of day 11 to synchronize after this many steps,
+    /// instead of running until they either synchronize or repeat a previous state
+    #[structopt(long)]
+    pub octopus_limit: Option<usize>,
+
+    /// Replay the steps of day 11, whose flashes are counted, in the terminal
+    #[structopt(long)]
+    pub octopus_replay: bool,
+
+    /// Milliseconds between two steps of the replay of day 11
+    #[structopt(long, default_value = "100")]
+    pub octopus_delay: u64,
+
+    /// Save each step of the replay of day 11 as PPM image into this directory
+    #[structopt(long, parse(from_os_str))]
+    pub octopus_frames: Option<PathBuf>,
+
+    /// Record the replay of day 11 into this asciicast file, which can be played back
+    /// with asciinema
+    #[structopt(long, parse(from_os_str))]
+    pub octopus_cast: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            octopus_threshold: 9,
+            octopus_neighbourhood: Neighbourhood::Moore,
+            octopus_wrap: false,
+            octopus_steps: 100,
+            octopus_limit: None,
+            octopus_replay: false,
+            octopus_delay: 100,
+            octopus_frames: None,
+            octopus_cast: None,
+        }
+    }
+}
+
+/// The cells, which are adjacent to a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+pub enum Neighbourhood {
+    /// The four orthogonally adjacent cells
+    #[strum(serialize = "4")]
+    VonNeumann,
+    /// The eight cells around, including the diagonals
+    #[strum(serialize = "8")]
+    Moore,
+}
+
+/// The rules of the automaton
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+    threshold: u32,
+    neighbourhood: Neighbourhood,
+    wrap: bool,
+}
+
+/// How a simulation ended, which waited for the cells to synchronize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    /// All cells flashed at once in this step
+    Synchronized(usize),
+    /// The state after step `start` repeats every `period` steps without ever
+    /// synchronizing
+    Cycle { start: usize, period: usize },
+    /// The cells did not synchronize within this many steps
+    Limit(usize),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Synchronized(step) => write!(f, "synchronized in step {}", step),
+            Self::Cycle { start, period } => write!(
+                f,
+                "never synchronized, the state after step {} repeats every {} steps",
+                start, period
+            ),
+            Self::Limit(steps) => write!(f, "not synchronized within {} steps", steps),
+        }
+    }
+}
+
+/// A cellular automaton, whose cells gain energy each step and flash, once their energy
+/// exceeds the threshold. Each flash feeds energy to the adjacent cells, which may flash
+/// in turn, but each cell flashes at most once per step.
+#[derive(Debug, Clone)]
+struct Automaton {
+    energy: Array2<u32>,
+    /// The cells, which flashed during the last step
+    flashed: Array2<bool>,
+    /// The adjacent cells of each cell, which are precomputed for the cascade
+    neighbours: Array2<Vec<(usize, usize)>>,
+    threshold: u32,
+    /// The number of steps simulated so far
+    steps: usize,
+}
+
+impl Automaton {
+    fn new(energy: Array2<u32>, rules: Rules) -> Self {
+        let (rows, columns) = energy.dim();
+        let neighbours = Array2::from_shape_fn((rows, columns), |(i, j)| {
+            let mut neighbours = Vec::new();
+            for (di, dj) in (-1isize..=1).flat_map(|di| (-1isize..=1).map(move |dj| (di, dj))) {
+                if (di, dj) == (0, 0)
+                    || (rules.neighbourhood == Neighbourhood::VonNeumann && di != 0 && dj != 0)
+                {
+                    continue;
+                }
+                let (mut ni, mut nj) = (i as isize + di, j as isize + dj);
+                if rules.wrap {
+                    ni = ni.rem_euclid(rows as isize);
+                    nj = nj.rem_euclid(columns as isize);
+                }
+                let neighbour = (ni as usize, nj as usize);
+                // on narrow maps, wrapping around may lead back to the cell itself, or to
+                // the same neighbour from both sides
+                if ni >= 0
+                    && nj >= 0
+                    && energy.get(neighbour).is_some()
+                    && neighbour != (i, j)
+                    && !neighbours.contains(&neighbour)
+                {
+                    neighbours.push(neighbour);
+                }
+            }
+            neighbours
+        });
+
+        Self {
+            flashed: Array2::from_elem(energy.raw_dim(), false),
+            energy,
+            neighbours,
+            threshold: rules.threshold,
+            steps: 0,
+        }
+    }
+
+    /// Simulate a single step. Returns the number of flashes.
+    fn step(&mut self) -> usize {
+        self.flashed.fill(false);
+        self.steps += 1;
+
+        // increase all energy levels by 1 and queue the cells, which flash right away
+        self.energy += 1;
+        let mut queue = VecDeque::new();
+        for (index, _) in self
+            .energy
+            .indexed_iter()
+            .filter(|(_, &e)| e > self.threshold)
+        {
+            self.flashed[index] = true;
+            queue.push_back(index);
+        }
+
+        // feed the energy of each flash to the adjacent cells, which may flash in turn
+        let mut flashes = 0;
+        while let Some(index) = queue.pop_front() {
+            flashes += 1;
+            for &neighbour in &self.neighbours[index] {
+                self.energy[neighbour] += 1;
+                if self.energy[neighbour] > self.threshold && !self.flashed[neighbour] {
+                    self.flashed[neighbour] = true;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        // reset energy of flashed cells
+        for (energy, _) in self
+            .energy
+            .iter_mut()
+            .zip(&self.flashed)
+            .filter(|(_, &f)| f)
+        {
+            *energy = 0;
+        }
+
+        flashes
+    }
+
+    /// Simulate, until all cells flash in the same step, a previous state repeats, or
+    /// the limit of steps is reached
+    fn synchronize(&mut self, limit: Option<usize>) -> Outcome {
+        let mut seen = HashMap::new();
+        seen.insert(self.energy.clone(), self.steps);
+        while limit.is_none_or(|limit| self.steps < limit) {
+            if self.step() == self.energy.len() {
+                return Outcome::Synchronized(self.steps);
+            }
+            if let Some(start) = seen.insert(self.energy.clone(), self.steps) {
+                return Outcome::Cycle {
+                    start,
+                    period: self.steps - start,
+                };
+            }
+        }
+        Outcome::Limit(self.steps)
+    }
+}
+
+impl Automaton {
+    /// Write the energy levels of the cells in ANSI escape codes, which highlight the
+    /// flashes of the last step. Energy levels above 9 are written as letters.
+    fn write_ansi(&self, output: &mut impl Write) -> io::Result<()> {
+        for (i, row) in self.energy.rows().into_iter().enumerate() {
+            for (j, &energy) in row.iter().enumerate() {
+                let level = char::from_digit(energy.min(35), 36).unwrap();
+                if self.flashed[(i, j)] {
+                    write!(output, "\x1b[1;30;103m{}\x1b[0m", level)?;
+                } else {
+                    write!(output, "\x1b[2m{}\x1b[0m", level)?;
+                }
+            }
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+
+    /// Write a PPM image with a square of `scale` pixels per cell. Flashed cells are
+    /// yellow, all others get brighter with their energy.
+    fn write_image(&self, output: &mut impl Write, scale: usize) -> io::Result<()> {
+        let (rows, columns) = self.energy.dim();
+        writeln!(output, "P6\n{} {}\n255", columns * scale, rows * scale)?;
+        for row in 0..rows * scale {
+            for column in 0..columns * scale {
+                let index = (row / scale, column / scale);
+                let pixel = if self.flashed[index] {
+                    [255, 255, 96]
+                } else {
+                    // initial levels may exceed a lowered threshold
+                    let level = (self.energy[index] * 160 / self.threshold.max(1)).min(255) as u8;
+                    [level / 2, level / 2, level]
+                };
+                output.write_all(&pixel)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A replay of the simulation, which shows each step in the terminal, saves it as image
+/// and records it as asciicast
+struct Replay {
+    terminal: bool,
+    delay: Duration,
+    frames: Option<PathBuf>,
+    cast: Option<BufWriter<File>>,
+}
+
+impl Replay {
+    /// The number of pixels per cell of the images
+    const SCALE: usize = 8;
+
+    /// Prepare the replay of the automaton. Returns None, if no replay was requested.
+    fn new(options: &Options, automaton: &Automaton) -> io::Result<Option<Self>> {
+        if !options.octopus_replay
+            && options.octopus_frames.is_none()
+            && options.octopus_cast.is_none()
+        {
+            return Ok(None);
+        }
+
+        // the header of asciicast v2, the frame is followed by its annotation
+        let cast = match &options.octopus_cast {
+            Some(path) => {
+                let mut cast = BufWriter::new(File::create(path)?);
+                let (rows, columns) = automaton.energy.dim();
+                let header = serde_json::json!({
+                    "version": 2,
+                    "width": columns.max(24),
+                    "height": rows + 1,
+                });
+                writeln!(cast, "{}", header)?;
+                Some(cast)
+            }
+            None => None,
+        };
+
+        Ok(Some(Self {
+            terminal: options.octopus_replay,
+            delay: Duration::from_millis(options.octopus_delay),
+            frames: options.octopus_frames.clone(),
+            cast,
+        }))
+    }
+
+    /// Show the state of the automaton after its last step with the number of flashes
+    async fn show(&mut self, automaton: &Automaton, flashes: usize) -> io::Result<()> {
+        let mut frame = b"\x1b[H\x1b[2J".to_vec();
+        automaton.write_ansi(&mut frame)?;
+        write!(frame, "Step {}: {} flashes", automaton.steps, flashes)?;
+
+        if let Some(dir) = &self.frames {
+            let path = dir.join(format!("octopus-{:04}.ppm", automaton.steps));
+            let mut file = BufWriter::new(File::create(path)?);
+            automaton.write_image(&mut file, Self::SCALE)?;
+            file.flush()?;
+        }
+        if let Some(cast) = &mut self.cast {
+            // terminals need a carriage return for each new line
+            let time = (automaton.steps as u32 * self.delay).as_secs_f64();
+            let data = String::from_utf8_lossy(&frame).replace('\n', "\r\n");
+            writeln!(cast, "{}", serde_json::json!([time, "o", data]))?;
+        }
+        if self.terminal {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&frame)?;
+            writeln!(stdout)?;
+            stdout.flush()?;
+            tokio::time::sleep(self.delay).await;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self.cast {
+            Some(mut cast) => cast.flush(),
+            None => Ok(()),
+        }
+    }
+}
 
 /// Executes the exercise of day 11
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
@@ -25,65 +385,329 @@ pub async fn execute<E: Error + 'static>(
         // add row, or construct map
         map.as_mut().unwrap().push_row(ArrayView::from(&row))?;
     }
-    let mut map = map.unwrap();
+    let rules = Rules {
+        threshold: options.octopus_threshold,
+        neighbourhood: options.octopus_neighbourhood,
+        wrap: options.octopus_wrap,
+    };
+    let octopuses = Automaton::new(map.unwrap(), rules);
+
+    // count the flashes within the first steps, which may be replayed
+    let mut counter = octopuses.clone();
+    let mut replay = Replay::new(options, &counter)?;
+    if let Some(replay) = &mut replay {
+        replay.show(&counter, 0).await?;
+    }
+    let mut flashes = 0;
+    for _ in 0..options.octopus_steps {
+        let step = counter.step();
+        flashes += step;
+        if let Some(replay) = &mut replay {
+            replay.show(&counter, step).await?;
+        }
+    }
+    if let Some(replay) = replay {
+        replay.finish()?;
+    }
 
-    // run the simulation for some steps
-    let mut flashes_100 = 0;
-    let mut all_flash = 0;
-    let mut flashed: Array2<bool> = Array2::from_elem(map.raw_dim(), false);
-    for i in 0.. {
-        // reset the flashed marker map
-        flashed.fill(false);
+    // wait for all octopuses to flash at once
+    let mut synchronizer = octopuses;
+    match synchronizer.synchronize(options.octopus_limit) {
+        Outcome::Synchronized(step) => Ok([flashes as u64, step as u64]),
+        outcome => Err(format!("The octopuses {}", outcome).into()),
+    }
+}
+
+/// Generate a `size` x `size` map of energy levels, whose octopuses synchronize
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    // the steps a map may take to synchronize, before it is rejected
+    const LIMIT: usize = 1000;
+    let rules = Rules {
+        threshold: 9,
+        neighbourhood: Neighbourhood::Moore,
+        wrap: false,
+    };
+
+    // large maps of random levels rarely synchronize, so blend them into a uniform level
+    // more and more, until they do. A uniform map synchronizes within 10 steps.
+    let size = options.size;
+    let mut noise = 1.0;
+    let map = loop {
+        let level = rng.gen_range(0..10);
+        let map = Array2::from_shape_simple_fn((size, size), || {
+            if rng.gen_bool(noise) {
+                rng.gen_range(0..10)
+            } else {
+                level
+            }
+        });
+        let outcome = Automaton::new(map.clone(), rules).synchronize(Some(LIMIT));
+        if let Outcome::Synchronized(_) = outcome {
+            break map;
+        }
+        noise /= 2.0;
+    };
+
+    for row in map.rows() {
+        let row: String = row
+            .iter()
+            .map(|&level| char::from_digit(level, 10).unwrap())
+            .collect();
+        writeln!(output, "{}", row)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::{generated, lines};
+    use proptest::prelude::*;
 
-        // increase all energy levels by 1
-        map += 1;
+    const EXAMPLE: &str = "\
+5483143223
+2745854711
+5264556173
+6141336146
+6357385478
+4167524645
+2176841721
+6882881134
+4846848554
+5283751526
+";
 
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines(EXAMPLE), &Options::default()).await.unwrap(),
+            [1656, 195]
+        );
+    }
+
+    /// Reference, which rescans the whole map for new flashes in each round
+    fn step_by_rescanning(map: &mut Array2<u32>) -> usize {
+        let mut flashed = Array2::from_elem(map.raw_dim(), false);
+        *map += 1;
         loop {
-            // flash octopuses with energy above 9
             let new_flashes: Vec<_> = map
                 .indexed_iter()
-                .zip(flashed.iter_mut())
-                .filter(|((_, &e), f)| e > 9 && !**f)
-                .map(|((index, _), f)| {
-                    *f = true;
-                    index
-                })
+                .filter(|&(index, &e)| e > 9 && !flashed[index])
+                .map(|(index, _)| index)
                 .collect();
-
-            // increase the energy level of all octopuses adjacent to the flash
-            for (i, j) in &new_flashes {
-                let left = if *i > 0 { i - 1 } else { 0 };
-                let right = (i + 2).min(map.shape()[0]);
-                let top = if *j > 0 { j - 1 } else { 0 };
-                let bottom = (j + 2).min(map.shape()[1]);
-                map.slice_mut(s![left..right, top..bottom])
-                    .map_inplace(|e| *e += 1);
-            }
-
-            // break the loop, if no new octopuses flashed
             if new_flashes.is_empty() {
                 break;
             }
+            for (i, j) in new_flashes {
+                flashed[(i, j)] = true;
+                let rows = i.saturating_sub(1)..(i + 2).min(map.nrows());
+                let columns = j.saturating_sub(1)..(j + 2).min(map.ncols());
+                map.slice_mut(s![rows, columns]).map_inplace(|e| *e += 1);
+            }
+        }
+        map.zip_mut_with(&flashed, |e, &f| {
+            if f {
+                *e = 0
+            }
+        });
+        flashed.iter().filter(|&&f| f).count()
+    }
+
+    fn rules(neighbourhood: Neighbourhood, wrap: bool) -> Rules {
+        Rules {
+            threshold: 9,
+            neighbourhood,
+            wrap,
         }
+    }
 
-        // reset energy of flashed octopuses
-        map.iter_mut()
-            .zip(flashed.iter())
-            .filter(|(_, &f)| f)
-            .for_each(|(e, _)| *e = 0);
+    #[tokio::test]
+    async fn fewer_steps() {
+        let options = Options {
+            octopus_steps: 10,
+            ..Options::default()
+        };
+        assert_eq!(execute(lines(EXAMPLE), &options).await.unwrap(), [204, 195]);
+    }
+
+    #[test]
+    fn neighbourhoods() {
+        let count = |neighbourhood, wrap| {
+            let automaton = Automaton::new(Array2::zeros((3, 4)), rules(neighbourhood, wrap));
+            automaton.neighbours.map(Vec::len)
+        };
+        assert_eq!(
+            count(Neighbourhood::Moore, false),
+            arr2(&[[3, 5, 5, 3], [5, 8, 8, 5], [3, 5, 5, 3]])
+        );
+        assert_eq!(
+            count(Neighbourhood::VonNeumann, false),
+            arr2(&[[2, 3, 3, 2], [3, 4, 4, 3], [2, 3, 3, 2]])
+        );
+        assert_eq!(
+            count(Neighbourhood::Moore, true),
+            Array2::from_elem((3, 4), 8)
+        );
+        assert_eq!(
+            count(Neighbourhood::VonNeumann, true),
+            Array2::from_elem((3, 4), 4)
+        );
+
+        // on a single row, wrapping around reaches the same cells from both sides
+        let automaton = Automaton::new(Array2::zeros((1, 2)), rules(Neighbourhood::Moore, true));
+        assert_eq!(automaton.neighbours[(0, 0)], [(0, 1)]);
+    }
+
+    #[test]
+    fn cycles_and_limits() {
+        let rules = Rules {
+            threshold: 2,
+            ..rules(Neighbourhood::VonNeumann, false)
+        };
+        assert_eq!(
+            Automaton::new(arr2(&[[1, 1, 1]]), rules).synchronize(None),
+            Outcome::Synchronized(2)
+        );
+
+        // the flashes alternate between the left octopus and the two others
+        let map = arr2(&[[2, 0, 0]]);
+        assert_eq!(
+            Automaton::new(map.clone(), rules).synchronize(None),
+            Outcome::Cycle {
+                start: 0,
+                period: 2
+            }
+        );
+        assert_eq!(
+            Automaton::new(map, rules).synchronize(Some(1)),
+            Outcome::Limit(1)
+        );
+    }
 
-        // count flashed octopuses
-        let flashes = flashed.iter().filter(|f| **f).count() as u64;
-        if i < 100 {
-            flashes_100 += flashes;
+    #[tokio::test]
+    async fn never_synchronized() {
+        let options = Options {
+            octopus_threshold: 2,
+            octopus_neighbourhood: Neighbourhood::VonNeumann,
+            ..Options::default()
+        };
+        let error = execute(lines("200\n"), &options).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The octopuses never synchronized, the state after step 0 repeats every 2 steps"
+        );
+    }
+
+    #[test]
+    fn frames() {
+        let mut automaton = Automaton::new(arr2(&[[9, 0]]), rules(Neighbourhood::Moore, false));
+        automaton.step();
+
+        let mut text = Vec::new();
+        automaton.write_ansi(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "\x1b[1;30;103m0\x1b[0m\x1b[2m2\x1b[0m\n"
+        );
+
+        let mut image = Vec::new();
+        automaton.write_image(&mut image, 2).unwrap();
+        let mut expected = b"P6\n4 2\n255\n".to_vec();
+        let row = [[255, 255, 96]; 2].into_iter().chain([[17, 17, 35]; 2]);
+        expected.extend(row.clone().chain(row).flatten());
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn frame_above_threshold() {
+        let rules = Rules {
+            threshold: 2,
+            ..rules(Neighbourhood::Moore, false)
+        };
+        let automaton = Automaton::new(arr2(&[[9, 1]]), rules);
+        let mut image = Vec::new();
+        automaton.write_image(&mut image, 1).unwrap();
+        let mut expected = b"P6\n2 1\n255\n".to_vec();
+        expected.extend([127, 127, 255, 40, 40, 80]);
+        assert_eq!(image, expected);
+    }
+
+    #[tokio::test]
+    async fn recorded_replay() {
+        let dir = std::env::temp_dir().join(format!("octopus-replay-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let options = Options {
+            octopus_steps: 3,
+            octopus_delay: 500,
+            octopus_frames: Some(dir.clone()),
+            octopus_cast: Some(dir.join("replay.cast")),
+            ..Options::default()
+        };
+        execute(lines(EXAMPLE), &options).await.unwrap();
+
+        // a header and the initial state, followed by each step
+        let cast = std::fs::read_to_string(dir.join("replay.cast")).unwrap();
+        let events: Vec<serde_json::Value> = cast
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0]["version"], 2);
+        assert_eq!(events[0]["height"], 11);
+        assert_eq!(events[4][0], 1.5);
+        assert!(events[2][2]
+            .as_str()
+            .unwrap()
+            .ends_with("Step 1: 0 flashes"));
+        assert!(events[3][2]
+            .as_str()
+            .unwrap()
+            .ends_with("Step 2: 35 flashes"));
+        for step in 0..=3 {
+            let frame = std::fs::read(dir.join(format!("octopus-{:04}.ppm", step))).unwrap();
+            assert!(frame.starts_with(b"P6\n80 80\n255\n"));
         }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 
-        // exit simulation, if all octopuses flash
-        if flashes == map.shape().iter().product::<usize>() as u64 {
-            all_flash = i + 1;
-            break;
+    proptest! {
+        #[test]
+        fn cascade_matches_rescanning(
+            map in prop::collection::vec(0u32..10, 1..64).prop_map(|v| {
+                let columns = (v.len() as f64).sqrt() as usize;
+                let cells: Vec<_> = v.into_iter().take(columns * columns).collect();
+                Array2::from_shape_vec((columns, columns), cells).unwrap()
+            }),
+            steps in 1usize..30,
+        ) {
+            let mut automaton = Automaton::new(map.clone(), rules(Neighbourhood::Moore, false));
+            let mut reference = map;
+            for _ in 0..steps {
+                prop_assert_eq!(automaton.step(), step_by_rescanning(&mut reference));
+                prop_assert_eq!(&automaton.energy, &reference);
+            }
         }
     }
 
-    Ok([flashes_100, all_flash])
+    #[test]
+    fn generated_input() {
+        let input = generated(generate, 20);
+        assert_eq!(input.lines().count(), 20);
+        assert!(input
+            .lines()
+            .all(|line| line.len() == 20 && line.chars().all(|c| c.is_ascii_digit())));
+    }
+
+    #[tokio::test]
+    async fn generated_input_synchronizes() {
+        for size in [1, 10, 30] {
+            let input = generated(generate, size);
+            execute(lines(&input), &Options::default()).await.unwrap();
+        }
+    }
 }
@@ -1,166 +1,484 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
-use std::{collections::HashMap, error::Error};
+use rand::{seq::SliceRandom, Rng, RngCore};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    io::{self, Write},
+    ops::Deref,
+};
+use structopt::StructOpt;
+
+// Options of the cave paths. Not a doc comment, as it is flattened into the options of
+// the command line.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    /// List all paths through the caves of day 12, instead of only counting them
+    #[structopt(long)]
+    pub caves_paths: bool,
+}
 
 /// Some common cave names
 const START: &str = "start";
 const END: &str = "end";
 
-/// A path trough the cave system
-#[derive(Clone, Debug)]
-struct Path<'a> {
-    pos: &'a str,
-    histogram: HashMap<&'a str, usize>,
-    ended: bool,
-    progressed: bool,
+/// The cave system with the caves interned to their index
+#[derive(Debug, Default)]
+struct CaveSystem {
+    names: Vec<String>,
+    /// The bit of each small cave in the set of visited caves, which is 0 for big caves
+    bits: Vec<Visited>,
+    connections: Vec<Vec<usize>>,
+    ids: HashMap<String, usize>,
+    /// The number of small caves
+    small: u32,
 }
 
-impl<'a> Path<'a> {
-    /// Create a new path an the start of the cave system
-    fn new() -> Self {
-        Self {
-            pos: START,
-            histogram: HashMap::from([(START, 1)]),
-            ended: false,
-            progressed: false,
+/// The small caves visited by a path as bit set
+type Visited = u128;
+
+/// The maximal number of memoized states while counting the paths, which bounds the
+/// memory to about a hundred megabytes
+const MAX_STATES: usize = 1 << 20;
+
+impl CaveSystem {
+    /// Get the index of a cave, which is added, if it is not known yet
+    fn intern(&mut self, name: &str) -> Result<usize, String> {
+        if let Some(&id) = self.ids.get(name) {
+            return Ok(id);
         }
-    }
 
-    /// Progress the path to the new location
-    fn progress(&mut self, pos: &'a str) {
-        // ended paths cannot progress
-        if self.ended {
-            return;
+        // assign the next bit to small caves
+        let mut bit = 0;
+        if name.chars().all(char::is_lowercase) {
+            if self.small == Visited::BITS {
+                return Err(format!(
+                    "Only up to {} small caves are supported",
+                    Visited::BITS
+                ));
+            }
+            bit = 1 << self.small;
+            self.small += 1;
         }
 
-        // update current position and mark path as progressed
-        self.pos = pos;
-        self.progressed = true;
+        let id = self.names.len();
+        self.ids.insert(name.to_owned(), id);
+        self.names.push(name.to_owned());
+        self.bits.push(bit);
+        self.connections.push(Vec::new());
+        Ok(id)
+    }
 
-        // update histogramm of small caves
-        if pos.chars().all(char::is_lowercase) {
-            *self.histogram.entry(pos).or_default() += 1;
+    /// Connect two caves in both directions
+    fn connect(&mut self, a: &str, b: &str) -> Result<(), String> {
+        let (a, b) = (self.intern(a)?, self.intern(b)?);
+        if self.bits[a] == 0 && self.bits[b] == 0 {
+            return Err(format!(
+                "The big caves {} and {} are connected, so there are infinitely many paths",
+                self.names[a], self.names[b]
+            ));
         }
+        self.connections[a].push(b);
+        self.connections[b].push(a);
+        Ok(())
+    }
 
-        // check, if path has ended
-        if pos == END {
-            self.ended = true;
+    /// The start and the end cave, if both exist
+    fn ends(&self) -> Option<(usize, usize)> {
+        Some((*self.ids.get(START)?, *self.ids.get(END)?))
+    }
+
+    /// Check, if a path may enter a cave. Small caves may be entered once, but a single
+    /// one of them twice, if a revisit is left. The start can't be entered again.
+    /// Returns the visited caves and, if a revisit is still left, after entering it.
+    fn enter(&self, cave: usize, visited: Visited, revisit: bool) -> Option<(Visited, bool)> {
+        let bit = self.bits[cave];
+        if bit == 0 {
+            Some((visited, revisit))
+        } else if visited & bit == 0 {
+            Some((visited | bit, revisit))
+        } else if revisit && self.names[cave] != START {
+            Some((visited, false))
+        } else {
+            None
         }
     }
-}
 
-/// Walk through all possible paths
-fn walk_paths(
-    caves: &HashMap<String, Vec<String>>,
-    valid: impl Fn(&Path, &str) -> bool,
-) -> Vec<Path> {
-    // Start one path at the beginning
-    let mut paths = vec![Path::new()];
-
-    // walk paths as long as there is progress
-    let mut progressed = true;
-    while progressed {
-        progressed = false;
-
-        // advance each path
-        for i in 0..paths.len() {
-            // skip paths, which already ended
-            if paths[i].ended {
-                continue;
+    /// Count the paths from the start to the end, which may revisit a small cave, if
+    /// `revisit` is set. The counts are memoized on the current cave, the visited
+    /// small caves and whether the revisit is still left. Fails, if the number of paths
+    /// overflows, or if more than `states` of these states would be memoized.
+    fn count_paths(&self, revisit: bool, states: usize) -> Result<u64, String> {
+        /// The number of paths from the cave to the end
+        fn count(
+            caves: &CaveSystem,
+            end: usize,
+            state: (usize, Visited, bool),
+            states: usize,
+            memo: &mut HashMap<(usize, Visited, bool), u64>,
+        ) -> Result<u64, String> {
+            let (cave, visited, revisit) = state;
+            if cave == end {
+                return Ok(1);
             }
-            paths[i].progressed = false;
-
-            // branch out to all possible connections
-            let mut first = true;
-            let path = paths[i].clone();
-            for con in &caves[path.pos] {
-                // verify path
-                if !valid(&path, con) {
-                    continue;
-                }
-                progressed = true;
-
-                // progress and replace the existing path or add new path to the vecotr
-                if first {
-                    paths[i].progress(con);
-                    first = false;
-                } else {
-                    paths.push(path.clone());
-                    paths.last_mut().unwrap().progress(con);
+            if let Some(&paths) = memo.get(&state) {
+                return Ok(paths);
+            }
+
+            let mut paths = 0u64;
+            for &next in &caves.connections[cave] {
+                if let Some((visited, revisit)) = caves.enter(next, visited, revisit) {
+                    let more = count(caves, end, (next, visited, revisit), states, memo)?;
+                    paths = paths
+                        .checked_add(more)
+                        .ok_or("There are too many paths to count them")?;
                 }
             }
+            if memo.len() == states {
+                return Err(format!(
+                    "The paths through the caves can't be counted within {} states",
+                    states
+                ));
+            }
+            memo.insert(state, paths);
+            Ok(paths)
         }
 
-        // get rid of all paths, that did not progress at all
-        paths.retain(|path| path.progressed || path.ended);
+        let (start, end) = match self.ends() {
+            Some(ends) => ends,
+            None => return Ok(0),
+        };
+        count(
+            self,
+            end,
+            (start, self.bits[start], revisit),
+            states,
+            &mut HashMap::new(),
+        )
     }
 
-    paths
-}
-
-/// Validator for part 1
-#[inline]
-fn valid_part_1(path: &Path, cave: &str) -> bool {
-    !path.histogram.contains_key(cave)
-}
-
-/// Validator for part 2
-#[inline]
-fn valid_part_2(path: &Path, cave: &str) -> bool {
-    // verify hist
-    if path.histogram.contains_key(cave) {
-        // "start" can only be visited once
-        if cave == START {
-            return false;
+    /// Walk all paths from the start to the end depth first, which may revisit a small
+    /// cave, if `revisit` is set
+    fn visit_paths(&self, revisit: bool, visit: &mut dyn FnMut(&[usize])) {
+        fn walk(
+            caves: &CaveSystem,
+            end: usize,
+            path: &mut Vec<usize>,
+            (visited, revisit): (Visited, bool),
+            visit: &mut dyn FnMut(&[usize]),
+        ) {
+            let cave = *path.last().unwrap();
+            if cave == end {
+                visit(path);
+                return;
+            }
+            for &next in &caves.connections[cave] {
+                if let Some(state) = caves.enter(next, visited, revisit) {
+                    path.push(next);
+                    walk(caves, end, path, state, visit);
+                    path.pop();
+                }
+            }
         }
 
-        // check, if there is another cave which was visited twice
-        if path.histogram.values().any(|count| *count > 1) {
-            return false;
+        if let Some((start, end)) = self.ends() {
+            walk(
+                self,
+                end,
+                &mut vec![start],
+                (self.bits[start], revisit),
+                visit,
+            );
         }
     }
-
-    true
 }
 
 /// Executes the exercise of day 12
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+    options: &Options,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
     // read in the cave layout
-    let mut caves = HashMap::new();
+    let mut caves = CaveSystem::default();
+    let mut number = 0;
     while let Some(line) = input.try_next().await? {
-        // get cave connections
-        let mut connection = line.split('-');
-        let start = connection.next().unwrap();
-        let end = connection.next().unwrap();
+        number += 1;
+        let (a, b) = line
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid connection {:?} in line {}", &*line, number))?;
+        caves.connect(a, b)?;
+    }
+    // list all distinct paths, if requested
+    if options.caves_paths {
+        for (part, revisit) in [(1, false), (2, true)] {
+            println!("Paths of part {}:", part);
+            caves.visit_paths(revisit, &mut |path| {
+                let names: Vec<_> = path
+                    .iter()
+                    .map(|&cave| caves.names[cave].as_str())
+                    .collect();
+                println!("  {}", names.join(","));
+            });
+        }
+    }
 
-        // add connection to mapping
+    // count all distinct paths through the cave system
+    let mut counts = [0; 2];
+    for (count, revisit) in counts.iter_mut().zip([false, true]) {
+        *count = caves.count_paths(revisit, MAX_STATES)?;
+    }
+
+    Ok(counts)
+}
+
+/// Generate a connected cave system with `size` small caves, but at most as many as fit
+/// into the set of visited caves. The caves form clusters of a few small caves around a
+/// big one, which are only connected through the start and the end. So each path stays
+/// within a single cluster, which keeps the paths countable, however large the system.
+/// Big caves are never connected to each other, so the number of paths is finite.
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    // the start and the end are small caves as well
+    let small = options.size.min(Visited::BITS as usize - 2);
+
+    // give each cave a unique name, which is long enough to get all of them
+    let length = ((small * 2) as f64 * 4.0).log(26.0).ceil().max(2.0) as usize;
+    let mut names = HashSet::from([START.to_owned(), END.to_owned()]);
+    let mut name = |rng: &mut dyn RngCore| loop {
+        let name: String = (0..length).map(|_| rng.gen_range('a'..='z')).collect();
+        if names.insert(name.clone()) {
+            break name;
+        }
+    };
+
+    let mut connections = Vec::new();
+    if small == 0 {
+        connections.push((START.to_owned(), END.to_owned()));
+    }
+    let mut remaining = small;
+    while remaining > 0 {
+        let size = rng.gen_range(3..=6).min(remaining);
+        remaining -= size;
+        let caves: Vec<String> = (0..size).map(|_| name(rng)).collect();
+        let big = name(rng).to_uppercase();
+
+        // connect each cave to one of the caves before, the big cave to some of them
+        let mut cluster = Vec::new();
+        for (i, cave) in caves.iter().enumerate().skip(1) {
+            cluster.push((caves[rng.gen_range(0..i)].clone(), cave.clone()));
+        }
+        let joined = rng.gen_range(1..=size.min(3));
+        for cave in caves.choose_multiple(rng, joined) {
+            cluster.push((big.clone(), cave.clone()));
+        }
+
+        // add a shortcut to get another loop through the cluster
+        let (a, b) = (caves.choose(rng).unwrap(), caves.choose(rng).unwrap());
+        let known = |(x, y): &(String, String)| (x == a && y == b) || (x == b && y == a);
+        if a != b && !cluster.iter().any(known) {
+            cluster.push((a.clone(), b.clone()));
+        }
+
+        cluster.push((START.to_owned(), caves.choose(rng).unwrap().clone()));
+        cluster.push((caves.choose(rng).unwrap().clone(), END.to_owned()));
+        connections.extend(cluster);
+    }
+    connections.shuffle(rng);
+
+    for (a, b) in connections {
+        writeln!(output, "{}-{}", a, b)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::{generated, lines};
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    const SMALL: &str = "\
+start-A
+start-b
+A-c
+A-b
+b-d
+A-end
+b-end
+";
+
+    const MEDIUM: &str = "\
+dc-end
+HN-start
+start-kj
+dc-start
+dc-HN
+LN-dc
+HN-end
+kj-sa
+kj-HN
+kj-dc
+";
+
+    const LARGE: &str = "\
+fs-end
+he-DX
+fs-he
+start-DX
+pj-DX
+end-zg
+zg-sl
+zg-pj
+pj-he
+RW-he
+fs-DX
+pj-RW
+zg-RW
+start-pj
+he-WI
+zg-he
+pj-fs
+start-RW
+";
+
+    #[tokio::test]
+    async fn examples() {
+        assert_eq!(
+            execute(lines(SMALL), &Options::default()).await.unwrap(),
+            [10, 36]
+        );
+        assert_eq!(
+            execute(lines(MEDIUM), &Options::default()).await.unwrap(),
+            [19, 103]
+        );
+        assert_eq!(
+            execute(lines(LARGE), &Options::default()).await.unwrap(),
+            [226, 3509]
+        );
+    }
+
+    /// Intern the caves of an input
+    fn caves(input: &str) -> CaveSystem {
+        let mut caves = CaveSystem::default();
+        for (a, b) in input.lines().map(|line| line.split_once('-').unwrap()) {
+            caves.connect(a, b).unwrap();
+        }
         caves
-            .entry(start.to_owned())
-            .or_insert_with(Vec::new)
-            .push(end.to_owned());
     }
 
-    // build a map of all possible cave connections
-    let mut connections = HashMap::new();
-    for (cave, conn) in caves {
-        for c in conn {
-            connections
-                .entry(cave.clone())
-                .or_insert_with(Vec::new)
-                .push(c.clone());
-            connections
-                .entry(c.clone())
-                .or_insert_with(Vec::new)
-                .push(cave.clone());
+    #[test]
+    fn listed_paths() {
+        let caves = caves(SMALL);
+        let mut paths = Vec::new();
+        caves.visit_paths(false, &mut |path| {
+            let names: Vec<_> = path
+                .iter()
+                .map(|&cave| caves.names[cave].as_str())
+                .collect();
+            paths.push(names.join(","));
+        });
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            [
+                "start,A,b,A,c,A,end",
+                "start,A,b,A,end",
+                "start,A,b,end",
+                "start,A,c,A,b,A,end",
+                "start,A,c,A,b,end",
+                "start,A,c,A,end",
+                "start,A,end",
+                "start,b,A,c,A,end",
+                "start,b,A,end",
+                "start,b,end",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_caves() {
+        let error = execute(lines("start-A\nA-B\nB-end\n"), &Options::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The big caves A and B are connected, so there are infinitely many paths"
+        );
+
+        let error = execute(lines("start-end\nstart\n"), &Options::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid connection \"start\" in line 2");
+
+        let input: String = (b'a'..b'm')
+            .flat_map(|a| (b'a'..b'm').map(move |b| format!("start-{}{}\n", a as char, b as char)))
+            .collect();
+        let error = execute(lines(&input), &Options::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Only up to 128 small caves are supported"
+        );
+
+        // without an end, there is no path at all
+        let input = "start-a\na-B\n";
+        assert_eq!(
+            execute(lines(input), &Options::default()).await.unwrap(),
+            [0, 0]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn counts_match_listed_paths(seed: u64, size in 1usize..9) {
+            let options = GeneratorOptions { seed, size, scale: None };
+            let mut input = Vec::new();
+            generate(&mut ChaCha8Rng::seed_from_u64(seed), &options, &mut input).unwrap();
+            let caves = caves(std::str::from_utf8(&input).unwrap());
+
+            for revisit in [false, true] {
+                let mut paths = Vec::new();
+                caves.visit_paths(revisit, &mut |path| paths.push(path.to_vec()));
+                let distinct: HashSet<_> = paths.iter().collect();
+                prop_assert_eq!(distinct.len(), paths.len());
+                prop_assert_eq!(caves.count_paths(revisit, MAX_STATES), Ok(paths.len() as u64));
+            }
         }
     }
 
-    // walk through the cave system and count all distinct paths
-    let paths1 = walk_paths(&connections, valid_part_1);
-    let paths2 = walk_paths(&connections, valid_part_2);
+    #[tokio::test]
+    async fn generated_input_is_countable() {
+        for size in [100, 1000] {
+            let input = generated(generate, size);
+            execute(lines(&input), &Options::default()).await.unwrap();
+        }
+    }
 
-    Ok([paths1.len() as u64, paths2.len() as u64])
+    #[test]
+    fn too_many_states() {
+        // all small caves connected to each other can be visited in any order
+        let names: Vec<_> = (b'a'..=b'y')
+            .map(|cave| (cave as char).to_string())
+            .collect();
+        let mut input = String::from("start-a\ny-end\n");
+        for (i, a) in names.iter().enumerate() {
+            for b in &names[i + 1..] {
+                input.push_str(&format!("{}-{}\n", a, b));
+            }
+        }
+        assert_eq!(
+            caves(&input).count_paths(false, 1000),
+            Err("The paths through the caves can't be counted within 1000 states".to_owned())
+        );
+        assert_eq!(caves(SMALL).count_paths(true, 1000), Ok(36));
+    }
 }
@@ -1,10 +1,49 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
 use ndarray::prelude::*;
-use std::error::Error;
+use rand::Rng;
+use std::{
+    error::Error,
+    io::{self, Write},
+    ops::Deref,
+};
+
+/// Fold the transparent paper along the given axis at the given position
+fn fold_paper(map: &Array2<bool>, axis: &str, pos: usize) -> Array2<bool> {
+    match axis {
+        "x" => {
+            // construct a new map with the correct dimensions
+            let mut new = Array2::from_elem((pos, map.shape()[1]), false);
+
+            // set dots
+            new.slice_mut(s![0..pos, ..])
+                .assign(&map.slice(s![0..pos, ..]));
+            new.slice_mut(s![0..pos, ..])
+                .into_iter()
+                .zip(map.slice(s![pos + 1..;-1, ..]))
+                .for_each(|(n, m)| *n |= *m);
+            new
+        }
+        "y" => {
+            // construct a new map with the correct dimensions
+            let mut new = Array2::from_elem((map.shape()[0], pos), false);
+
+            // set dots
+            new.slice_mut(s![.., 0..pos])
+                .assign(&map.slice(s![.., 0..pos]));
+            new.slice_mut(s![.., 0..pos])
+                .into_iter()
+                .zip(map.slice(s![.., pos + 1..;-1]))
+                .for_each(|(n, m)| *n |= *m);
+            new
+        }
+        _ => unreachable!(),
+    }
+}
 
 /// Executes the exercise of day 13
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
 ) -> Result<[u64; 2], Box<dyn Error>> {
     pin_mut!(input);
 
@@ -38,35 +77,7 @@ pub async fn execute<E: Error + 'static>(
         let pos: usize = fold.next().unwrap().parse().unwrap();
 
         // execute split
-        match axis {
-            "x" => {
-                // construct a new map with the correct dimensions
-                let mut new = Array2::from_elem((pos, map.shape()[1]), false);
-
-                // set dots
-                new.slice_mut(s![0..pos, ..])
-                    .assign(&map.slice(s![0..pos, ..]));
-                new.slice_mut(s![0..pos, ..])
-                    .into_iter()
-                    .zip(map.slice(s![pos + 1..;-1, ..]))
-                    .for_each(|(n, m)| *n |= *m);
-                map = new;
-            }
-            "y" => {
-                // construct a new map with the correct dimensions
-                let mut new = Array2::from_elem((map.shape()[0], pos), false);
-
-                // set dots
-                new.slice_mut(s![.., 0..pos])
-                    .assign(&map.slice(s![.., 0..pos]));
-                new.slice_mut(s![.., 0..pos])
-                    .into_iter()
-                    .zip(map.slice(s![.., pos + 1..;-1]))
-                    .for_each(|(n, m)| *n |= *m);
-                map = new;
-            }
-            _ => unreachable!(),
-        }
+        map = fold_paper(&map, axis, pos);
 
         if first.is_none() {
             first = Some(map.iter().filter(|e| **e).count());
@@ -90,3 +101,103 @@ pub async fn execute<E: Error + 'static>(
 
     Ok([first.unwrap() as u64, 0])
 }
+
+/// Generate `size` dots on a transparent paper of up to `scale` x `scale` and the
+/// instructions to fold it down to the size of the thermal camera code
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let scale = options.scale.unwrap_or(1000) as usize;
+
+    // unfold the paper of the code as long as it fits, each fold halves the paper
+    let mut shape = [40, 6];
+    let mut folds = Vec::new();
+    loop {
+        let axes: Vec<_> = (0..2).filter(|&a| 2 * shape[a] < scale).collect();
+        if axes.is_empty() {
+            break;
+        }
+        let axis = axes[rng.gen_range(0..axes.len())];
+        folds.push((axis, shape[axis]));
+        shape[axis] = 2 * shape[axis] + 1;
+    }
+
+    // the paper is only as large as the dots on it, so mark the far edges
+    if options.size > 0 {
+        writeln!(output, "{},{}", shape[0] - 1, rng.gen_range(0..shape[1]))?;
+        writeln!(output, "{},{}", rng.gen_range(0..shape[0]), shape[1] - 1)?;
+    }
+    for _ in 2..options.size {
+        writeln!(
+            output,
+            "{},{}",
+            rng.gen_range(0..shape[0]),
+            rng.gen_range(0..shape[1])
+        )?;
+    }
+
+    writeln!(output)?;
+    for (axis, pos) in folds.into_iter().rev() {
+        writeln!(output, "fold along {}={}", ["x", "y"][axis], pos)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+
+    const DOTS: &str = "\
+6,10
+0,14
+9,10
+0,3
+10,4
+4,11
+6,0
+6,12
+4,1
+0,13
+10,12
+3,4
+3,0
+8,4
+1,10
+2,14
+8,10
+9,0
+";
+
+    #[tokio::test]
+    async fn example() {
+        let input = format!("{}\nfold along y=7\nfold along x=5\n", DOTS);
+        assert_eq!(execute(lines(&input)).await.unwrap(), [17, 0]);
+    }
+
+    #[tokio::test]
+    async fn fold_x_first() {
+        let input = format!("{}\nfold along x=5\nfold along y=7\n", DOTS);
+        assert_eq!(execute(lines(&input)).await.unwrap(), [17, 0]);
+    }
+
+    #[test]
+    fn fold_both_axes() {
+        let mut map = Array2::from_elem((11, 15), false);
+        for dot in DOTS.lines() {
+            let (x, y) = dot.split_once(',').unwrap();
+            map[[x.parse().unwrap(), y.parse().unwrap()]] = true;
+        }
+
+        // folding along y and then x leaves a square outline
+        let map = fold_paper(&fold_paper(&map, "y", 7), "x", 5);
+        assert_eq!(map.shape(), &[5, 7]);
+        for ((x, y), dot) in map.indexed_iter() {
+            let outline = y < 5 && (x == 0 || x == 4 || y == 0 || y == 4);
+            assert_eq!(*dot, outline, "dot at {},{}", x, y);
+        }
+    }
+}
@@ -1,26 +1,16 @@
+use super::GeneratorOptions;
 use futures::{pin_mut, prelude::*};
-use std::{collections::HashMap, error::Error};
-
-/// Executes the exercise of day 13
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
-) -> Result<[u64; 2], Box<dyn Error>> {
-    pin_mut!(input);
-
-    // read in the polymere template
-    let poly = input.try_next().await?.unwrap();
-
-    // read in the insertion rules
-    input.try_next().await?;
-    let mut rules = HashMap::new();
-    while let Some(line) = input.try_next().await? {
-        let mut rule = line.split(" -> ");
-        rules.insert(
-            rule.next().unwrap().to_owned(),
-            rule.next().unwrap().chars().next().unwrap(),
-        );
-    }
+use rand::{seq::index, seq::SliceRandom, Rng};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{self, Write},
+    ops::Deref,
+};
 
+/// Apply the insertion rules to the polymer template for the given number of steps and
+/// get the histogram of the elements of the resulting polymer
+fn polymerize(poly: &str, rules: &HashMap<String, char>, steps: usize) -> HashMap<char, usize> {
     // construct pair and character histogram
     let mut pairs = HashMap::new();
     let mut chars = HashMap::new();
@@ -31,20 +21,15 @@ pub async fn execute<E: Error + 'static>(
     for char in poly.chars() {
         *chars.entry(char).or_insert(0usize) += 1;
     }
-    dbg!(&pairs);
-    dbg!(&chars);
 
     // apply rules
-    let mut part1 = 0;
-    let mut part2 = 0;
-    for i in 0..40 {
+    for _ in 0..steps {
         let mut new_pairs = pairs.clone();
-        for (pair, res) in &rules {
-            // remove current pair
-            let occ = if let Some(o) = pairs.get(pair) {
-                *o
-            } else {
-                continue;
+        for (pair, res) in rules {
+            // remove current pair, but skip pairs, which have been used up
+            let occ = match pairs.get(pair) {
+                Some(&o) if o > 0 => o,
+                _ => continue,
             };
 
             // construct new pairs
@@ -58,19 +43,145 @@ pub async fn execute<E: Error + 'static>(
             *chars.entry(*res).or_insert(0usize) += occ;
         }
         pairs = new_pairs;
+    }
+
+    chars
+}
+
+/// Get the difference between the most and the least occuring element
+fn spread(chars: &HashMap<char, usize>) -> usize {
+    chars.values().max().unwrap() - chars.values().min().unwrap()
+}
+
+/// Executes the exercise of day 13
+pub async fn execute<L: Deref<Target = str>, E: Error + 'static>(
+    input: impl Stream<Item = Result<L, E>>,
+) -> Result<[u64; 2], Box<dyn Error>> {
+    pin_mut!(input);
+
+    // read in the polymere template
+    let poly = input.try_next().await?.unwrap();
 
-        // update stats
-        if i == 9 || i == 39 {
-            // find least and most occuring character
-            let mut occurences: Vec<_> = chars.values().collect();
-            occurences.sort_unstable();
-            if i == 9 {
-                part1 = occurences[occurences.len() - 1] - occurences[0];
-            } else if i == 39 {
-                part2 = occurences[occurences.len() - 1] - occurences[0];
+    // read in the insertion rules
+    input.try_next().await?;
+    let mut rules = HashMap::new();
+    while let Some(line) = input.try_next().await? {
+        let mut rule = line.split(" -> ");
+        rules.insert(
+            rule.next().unwrap().to_owned(),
+            rule.next().unwrap().chars().next().unwrap(),
+        );
+    }
+
+    // apply rules and find the spread of least and most occuring character
+    let part1 = spread(&polymerize(&poly, &rules, 10));
+    let part2 = spread(&polymerize(&poly, &rules, 40));
+
+    Ok([part1 as u64, part2 as u64])
+}
+
+/// Generate a polymer template of length `size` made of `scale` distinct elements and
+/// an insertion rule for each pair of elements
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let elements: Vec<_> =
+        index::sample(rng, 26, options.scale.unwrap_or(10).clamp(1, 26) as usize)
+            .into_iter()
+            .map(|i| (b'A' + i as u8) as char)
+            .collect();
+
+    let template: String = (0..options.size.max(2))
+        .map(|_| elements.choose(rng).unwrap())
+        .collect();
+    writeln!(output, "{}\n", template)?;
+    for a in &elements {
+        for b in &elements {
+            writeln!(output, "{}{} -> {}", a, b, elements.choose(rng).unwrap())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::lines;
+    use proptest::{collection::hash_map, prelude::*};
+
+    /// Reference, which builds the whole polymer
+    fn polymerize_string(
+        poly: &str,
+        rules: &HashMap<String, char>,
+        steps: usize,
+    ) -> HashMap<char, usize> {
+        let mut poly: Vec<char> = poly.chars().collect();
+        for _ in 0..steps {
+            let mut new = vec![poly[0]];
+            for pair in poly.windows(2) {
+                if let Some(res) = rules.get(&pair.iter().collect::<String>()) {
+                    new.push(*res);
+                }
+                new.push(pair[1]);
             }
+            poly = new;
         }
+
+        let mut chars = HashMap::new();
+        for char in poly {
+            *chars.entry(char).or_insert(0) += 1;
+        }
+        chars
     }
 
-    Ok([part1 as u64, part2 as u64])
+    const EXAMPLE: &str = "\
+NNCB
+
+CH -> B
+HH -> N
+CB -> H
+NH -> C
+HB -> C
+HC -> B
+HN -> C
+NN -> C
+BH -> H
+NC -> B
+NB -> B
+BN -> B
+BB -> N
+BC -> B
+CC -> N
+CN -> C
+";
+
+    #[tokio::test]
+    async fn example() {
+        assert_eq!(
+            execute(lines(EXAMPLE)).await.unwrap(),
+            [1588, 2188189693529]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn polymerize_matches_reference(
+            poly in "[A-D]{1,8}",
+            rules in hash_map("[A-D]{2}", "[A-D]", 0..16),
+            steps in 0usize..8,
+        ) {
+            let rules = rules
+                .into_iter()
+                .map(|(pair, res)| (pair, res.chars().next().unwrap()))
+                .collect();
+            let fast = polymerize(&poly, &rules, steps);
+            let reference = polymerize_string(&poly, &rules, steps);
+
+            prop_assert_eq!(spread(&fast), spread(&reference));
+            prop_assert_eq!(fast, reference);
+        }
+    }
 }
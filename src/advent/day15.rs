@@ -1,5 +1,6 @@
-use futures::{pin_mut, prelude::*};
-use std::{collections::{HashMap, BinaryHeap}, error::Error};
+use super::GeneratorOptions;
+use rand::Rng;
+use std::{collections::{HashMap, BinaryHeap}, error::Error, io::{self, Write}};
 
 fn chiton_dijkstra(map: &HashMap<(i32, i32), i32>, origin: (i32, i32), destination: (i32, i32)) -> i32 {
     let mut heap: BinaryHeap::<(i32, (i32, i32))> = BinaryHeap::new(); // -dist, coords
@@ -21,7 +22,7 @@ fn chiton_dijkstra(map: &HashMap<(i32, i32), i32>, origin: (i32, i32), destinati
             if let Some(dist_ij) = map.get(&(xj, yj)) {
                 let dist_j = dist_i + dist_ij;
 
-                if dist_j < *distances.get(&(xj, yj)).unwrap_or(&std::i32::MAX) {
+                if dist_j < *distances.get(&(xj, yj)).unwrap_or(&i32::MAX) {
                     heap.push((-dist_j, (xj, yj)));
                     distances.insert((xj, yj), dist_j);
                 }
@@ -32,11 +33,11 @@ fn chiton_dijkstra(map: &HashMap<(i32, i32), i32>, origin: (i32, i32), destinati
     *distances.get(&destination).unwrap()
 }
 
-fn total_risk(chitons: &[Vec<u8>]) -> i32 {
+fn total_risk(chitons: &[&[u8]]) -> i32 {
     let map: HashMap<(i32, i32), i32> = chitons
         .iter().enumerate().flat_map(|(y, row)| {
             row.iter().enumerate().map(move |(x, &ch)| {
-                ((x as i32, y as i32), ch as i32)
+                ((x as i32, y as i32), (ch - b'0') as i32)
             })
         })
     .collect();
@@ -49,7 +50,7 @@ fn wrap_chiton_risk(tilex: i32, tiley: i32, risk: i32) -> i32 {
     1 + wrapped // back to 1..9 again
 }
 
-fn total_risk_full(chitons: &[Vec<u8>]) -> i32 {
+fn total_risk_full(chitons: &[&[u8]]) -> i32 {
     let w = chitons[0].len() as i32;
     let h = chitons.len() as i32;
     let map: HashMap<(i32, i32), i32> = chitons
@@ -59,7 +60,7 @@ fn total_risk_full(chitons: &[Vec<u8>]) -> i32 {
                     (0..5).map(move |tilex| {
                         (
                             (w * tilex + (x as i32), h * tiley + (y as i32)),
-                            wrap_chiton_risk(tilex, tiley, ch as i32)
+                            wrap_chiton_risk(tilex, tiley, (ch - b'0') as i32)
                         )
                     })
                 })
@@ -70,14 +71,50 @@ fn total_risk_full(chitons: &[Vec<u8>]) -> i32 {
 }
 
 /// Executes the exercise of day 15
-pub async fn execute<E: Error + 'static>(
-    input: impl Stream<Item = Result<String, E>>,
-) -> Result<[u64; 2], Box<dyn Error>> {
-    pin_mut!(input);
-
-    let chitons: Vec<Vec<u8>> = input.map_ok(|line| line.bytes().map(|b| b - b'0').collect()).try_collect().await?;
+pub fn execute(input: &str) -> Result<[u64; 2], Box<dyn Error>> {
+    // the risk levels are taken as ascii digits straight from the input
+    let chitons: Vec<&[u8]> = input.lines().map(str::as_bytes).collect();
     Ok([
         total_risk(&chitons) as u64,
         total_risk_full(&chitons) as u64,
     ])
 }
+
+/// Generate a `size` x `size` map of chiton risk levels
+pub fn generate(
+    rng: &mut impl Rng,
+    options: &GeneratorOptions,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    for _ in 0..options.size {
+        let row: String = (0..options.size)
+            .map(|_| char::from_digit(rng.gen_range(1..10), 10).unwrap())
+            .collect();
+        writeln!(output, "{}", row)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+1163751742
+1381373672
+2136511328
+3694931569
+7463417111
+1319128137
+1359912421
+3125421639
+1293138521
+2311944581
+";
+
+    #[test]
+    fn example() {
+        assert_eq!(execute(EXAMPLE).unwrap(), [40, 315]);
+    }
+}
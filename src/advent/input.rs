@@ -0,0 +1,99 @@
+use futures::{
+    prelude::*,
+    stream::{self, BoxStream},
+};
+use std::{borrow::Cow, io, path::Path};
+use tokio::{fs::File, io::BufReader};
+use tokio_util::codec::{FramedRead, LinesCodec, LinesCodecError};
+
+/// The puzzle input of a day, either read into memory as a whole or streamed line by line
+pub enum Input<'a> {
+    /// The whole input, which can be handed out without copying
+    Buffer(Cow<'a, str>),
+
+    /// The lines of an input file, which are read one after the other
+    Stream(BoxStream<'a, io::Result<String>>),
+}
+
+impl<'a> Input<'a> {
+    /// Read the whole input file into memory
+    pub async fn read(path: impl AsRef<Path>) -> io::Result<Input<'static>> {
+        Ok(Input::Buffer(tokio::fs::read_to_string(path).await?.into()))
+    }
+
+    /// Stream the input file line by line
+    pub async fn stream(path: impl AsRef<Path>) -> io::Result<Input<'static>> {
+        let file = BufReader::new(File::open(path).await?);
+
+        // Wrap input file into a lines codec to extract each line
+        let lines = FramedRead::new(file, LinesCodec::new()).map_err(|err| match err {
+            LinesCodecError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        });
+        Ok(Input::Stream(lines.boxed()))
+    }
+
+    /// Get the lines of the input one after the other. The lines of a buffer are borrowed
+    /// from it, only streamed lines are owned.
+    pub fn lines(&mut self) -> BoxStream<'_, io::Result<Cow<'_, str>>> {
+        match self {
+            Self::Buffer(buffer) => {
+                stream::iter(buffer.lines().map(|line| Ok(line.into()))).boxed()
+            }
+            Self::Stream(stream) => stream.map_ok(Cow::Owned).boxed(),
+        }
+    }
+
+    /// Get the whole input as text. This only copies the input, if it is streamed.
+    pub async fn text(&mut self) -> io::Result<Cow<'_, str>> {
+        match self {
+            Self::Buffer(buffer) => Ok(Cow::Borrowed(buffer)),
+            Self::Stream(stream) => {
+                let mut text = String::new();
+                while let Some(line) = stream.try_next().await? {
+                    text.push_str(&line);
+                    text.push('\n');
+                }
+                Ok(Cow::Owned(text))
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Input<'a> {
+    fn from(input: &'a str) -> Self {
+        Input::Buffer(input.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn buffer_and_stream_agree() {
+        let text = "first\n\nthird";
+        let stream = || {
+            Input::Stream(
+                super::super::lines(text)
+                    .map_ok(str::to_owned)
+                    .map_err(|err| match err {})
+                    .boxed(),
+            )
+        };
+
+        let mut input = Input::from(text);
+        let lines: Vec<_> = input.lines().try_collect().await.unwrap();
+        assert_eq!(lines, ["first", "", "third"]);
+        assert!(lines.iter().all(|line| matches!(line, Cow::Borrowed(_))));
+        let mut input = stream();
+        let lines: Vec<_> = input.lines().try_collect().await.unwrap();
+        assert_eq!(lines, ["first", "", "third"]);
+
+        assert!(matches!(
+            Input::from(text).text().await.unwrap(),
+            Cow::Borrowed("first\n\nthird")
+        ));
+        assert_eq!(stream().text().await.unwrap(), "first\n\nthird\n");
+    }
+}
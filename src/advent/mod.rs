@@ -1,6 +1,11 @@
-use futures::prelude::*;
-use std::{error::Error, path::PathBuf};
-use strum::{Display, EnumString};
+use rand::Rng;
+use std::{
+    error::Error,
+    io::{self, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+use strum::{Display, EnumIter, EnumString};
 
 mod day01;
 mod day02;
@@ -17,9 +22,13 @@ mod day12;
 mod day13;
 mod day14;
 mod day15;
+mod input;
+
+pub use day08::{scramble, ScrambleOptions};
+pub use input::Input;
 
 /// The day of the AdventOfCode calender to execut
-#[derive(Debug, EnumString, Display)]
+#[derive(Debug, Clone, Copy, EnumString, EnumIter, Display)]
 pub enum CalenderDay {
     #[strum(serialize = "01")]
     One,
@@ -54,27 +63,55 @@ pub enum CalenderDay {
 }
 
 impl CalenderDay {
-    /// Execute the exercise of the day
-    pub async fn execute<E: Error + 'static>(
+    /// Execute the exercise of the day. Days, which need the whole input at once, take it
+    /// straight from the buffer, all others get it line by line.
+    pub async fn execute(
         &self,
-        input: impl Stream<Item = Result<String, E>>,
+        mut input: Input<'_>,
+        options: &Options,
     ) -> Result<[u64; 2], Box<dyn Error>> {
         match self {
-            Self::One => day01::execute(input).await,
-            Self::Two => day02::execute(input).await,
-            Self::Three => day03::execute(input).await,
-            Self::Four => day04::execute(input).await,
-            Self::Five => day05::execute(input).await,
-            Self::Six => day06::execute(input).await,
-            Self::Seven => day07::execute(input).await,
-            Self::Eight => day08::execute(input).await,
-            Self::Nine => day09::execute(input).await,
-            Self::Ten => day10::execute(input).await,
-            Self::Eleven => day11::execute(input).await,
-            Self::Twelve => day12::execute(input).await,
-            Self::Thirteen => day13::execute(input).await,
-            Self::Fourteen => day14::execute(input).await,
-            Self::Fifteen => day15::execute(input).await,
+            Self::One => day01::execute(input.lines(), &options.sonar).await,
+            Self::Two => day02::execute(input.lines(), &options.submarine).await,
+            Self::Three => day03::execute(input.lines()).await,
+            Self::Four => day04::execute(&input.text().await?, &options.bingo),
+            Self::Five => day05::execute(input.lines(), &options.vents).await,
+            Self::Six => day06::execute(input.lines(), &options.lanternfish).await,
+            Self::Seven => day07::execute(input.lines(), &options.crabs).await,
+            Self::Eight => day08::execute(input.lines(), &options.display).await,
+            Self::Nine => day09::execute(input.lines(), &options.basins).await,
+            Self::Ten => day10::execute(input.lines(), &options.brackets).await,
+            Self::Eleven => day11::execute(input.lines(), &options.octopuses).await,
+            Self::Twelve => day12::execute(input.lines(), &options.caves).await,
+            Self::Thirteen => day13::execute(input.lines()).await,
+            Self::Fourteen => day14::execute(input.lines()).await,
+            Self::Fifteen => day15::execute(&input.text().await?),
+        }
+    }
+
+    /// Generate a random, but valid puzzle input for the exercise of the day
+    pub fn generate(
+        &self,
+        rng: &mut impl Rng,
+        options: &GeneratorOptions,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        match self {
+            Self::One => day01::generate(rng, options, output),
+            Self::Two => day02::generate(rng, options, output),
+            Self::Three => day03::generate(rng, options, output),
+            Self::Four => day04::generate(rng, options, output),
+            Self::Five => day05::generate(rng, options, output),
+            Self::Six => day06::generate(rng, options, output),
+            Self::Seven => day07::generate(rng, options, output),
+            Self::Eight => day08::generate(rng, options, output),
+            Self::Nine => day09::generate(rng, options, output),
+            Self::Ten => day10::generate(rng, options, output),
+            Self::Eleven => day11::generate(rng, options, output),
+            Self::Twelve => day12::generate(rng, options, output),
+            Self::Thirteen => day13::generate(rng, options, output),
+            Self::Fourteen => day14::generate(rng, options, output),
+            Self::Fifteen => day15::generate(rng, options, output),
         }
     }
 
@@ -83,3 +120,133 @@ impl CalenderDay {
         format!("input{}.txt", self).into()
     }
 }
+
+// The options of the exercises, which go beyond the puzzle of the day. Not a doc comment,
+// as it would replace the description of the command, which flattens the options.
+#[derive(Debug, Default, StructOpt)]
+pub struct Options {
+    #[structopt(flatten)]
+    pub sonar: day01::Options,
+
+    #[structopt(flatten)]
+    pub submarine: day02::Options,
+
+    #[structopt(flatten)]
+    pub bingo: day04::Options,
+
+    #[structopt(flatten)]
+    pub vents: day05::Options,
+
+    #[structopt(flatten)]
+    pub lanternfish: day06::Options,
+
+    #[structopt(flatten)]
+    pub crabs: day07::Options,
+
+    #[structopt(flatten)]
+    pub display: day08::Options,
+
+    #[structopt(flatten)]
+    pub basins: day09::Options,
+
+    #[structopt(flatten)]
+    pub brackets: day10::Options,
+
+    #[structopt(flatten)]
+    pub octopuses: day11::Options,
+
+    #[structopt(flatten)]
+    pub caves: day12::Options,
+}
+
+// The knobs to control the generation of random puzzle inputs. Not a doc comment,
+// as it would replace the description of the command, which flattens the knobs.
+#[derive(Debug, StructOpt)]
+pub struct GeneratorOptions {
+    /// Seed of the random number generator, the same seed always gives the same input
+    #[structopt(long, default_value = "0")]
+    pub seed: u64,
+
+    /// Number of entries to generate, like lines, bingo boards or the rows of a map
+    #[structopt(long, default_value = "100")]
+    pub size: usize,
+
+    /// Magnitude of the generated values, like coordinates, bingo numbers or the
+    /// bit width of diagnostic codes. Each day has a sensible default.
+    #[structopt(long)]
+    pub scale: Option<u32>,
+}
+
+/// Helper to feed an input, which is already in memory, into an exercise line by line,
+/// the same way the input files are read
+#[cfg(test)]
+pub fn lines(input: &str) -> impl futures::Stream<Item = Result<&str, std::convert::Infallible>> {
+    futures::stream::iter(input.lines().map(Ok))
+}
+
+/// Helper to generate a random puzzle input with the given number of entries
+#[cfg(test)]
+fn generated(
+    generate: impl FnOnce(
+        &mut rand_chacha::ChaCha8Rng,
+        &GeneratorOptions,
+        &mut Vec<u8>,
+    ) -> io::Result<()>,
+    size: usize,
+) -> String {
+    use rand::SeedableRng;
+
+    let options = GeneratorOptions {
+        seed: 0,
+        size,
+        scale: None,
+    };
+    let mut output = Vec::new();
+    generate(
+        &mut rand_chacha::ChaCha8Rng::seed_from_u64(options.seed),
+        &options,
+        &mut output,
+    )
+    .unwrap();
+
+    String::from_utf8(output).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use strum::IntoEnumIterator;
+
+    /// Generate the input of a day with the given seed
+    fn generate(day: CalenderDay, seed: u64) -> Vec<u8> {
+        let options = GeneratorOptions {
+            seed,
+            size: 30,
+            scale: None,
+        };
+        let mut output = Vec::new();
+        day.generate(&mut ChaCha8Rng::seed_from_u64(seed), &options, &mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn generator_is_deterministic() {
+        for day in CalenderDay::iter() {
+            assert_eq!(generate(day, 42), generate(day, 42), "day {}", day);
+            assert_ne!(generate(day, 42), generate(day, 43), "day {}", day);
+        }
+    }
+
+    #[tokio::test]
+    async fn generated_inputs_are_valid() {
+        for day in CalenderDay::iter() {
+            let input = String::from_utf8(generate(day, 0)).unwrap();
+            if let Err(err) = day.execute(Input::from(&*input), &Options::default()).await {
+                panic!("day {} fails on its generated input: {}", day, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use crate::advent::{self, CalenderDay, GeneratorOptions, Input};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+use strum::IntoEnumIterator;
+
+/// Run the exercises several times and compare their timings against a saved baseline
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// The days of the calender to benchmark, all days if none are given
+    days: Vec<CalenderDay>,
+
+    /// Number of runs of each day
+    #[structopt(short, long, default_value = "10")]
+    runs: usize,
+
+    /// Directory of the input files
+    #[structopt(long, default_value = "./inputs")]
+    inputs: PathBuf,
+
+    /// Benchmark on generated inputs instead of the input files
+    #[structopt(long)]
+    generated: bool,
+
+    #[structopt(flatten)]
+    generator: GeneratorOptions,
+
+    /// The baseline to compare the timings against
+    #[structopt(long, default_value = "./bench_baseline.json")]
+    baseline: PathBuf,
+
+    /// Save the timings as the new baseline
+    #[structopt(long)]
+    save: bool,
+
+    /// Relative slowdown of the median, which is flagged as regression
+    #[structopt(long, default_value = "0.1")]
+    threshold: f64,
+}
+
+/// Timing statistics of a phase of an exercise in nanoseconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Statistics {
+    min: u64,
+    median: u64,
+    p95: u64,
+}
+
+impl Statistics {
+    /// Calculate the statistics of the given timings
+    fn new(timings: &[Duration]) -> Self {
+        let mut timings: Vec<_> = timings.iter().map(|t| t.as_nanos() as u64).collect();
+        timings.sort_unstable();
+
+        // use the nearest rank for the 95th percentile
+        let p95 = (timings.len() * 95).div_ceil(100).max(1) - 1;
+        Self {
+            min: timings[0],
+            median: timings[timings.len() / 2],
+            p95: timings[p95],
+        }
+    }
+}
+
+/// The timing statistics of each phase of each day
+type Timings = BTreeMap<String, BTreeMap<String, Statistics>>;
+
+/// The inputs, which the exercises were benchmarked on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Source {
+    /// The input files
+    Files,
+    /// Inputs generated with these knobs
+    Generated {
+        seed: u64,
+        size: usize,
+        scale: Option<u32>,
+    },
+}
+
+/// The timings of a benchmark together with the inputs they were measured on, as
+/// timings are only comparable on the same inputs
+#[derive(Debug, Serialize, Deserialize)]
+struct Baseline {
+    source: Source,
+    timings: Timings,
+}
+
+/// Find all phases, whose median got slower than the baseline by more than the threshold
+fn regressions(timings: &Timings, baseline: &Timings, threshold: f64) -> Vec<(String, String)> {
+    let mut regressions = Vec::new();
+    for (day, phases) in timings {
+        for (phase, stats) in phases {
+            let base = baseline.get(day).and_then(|phases| phases.get(phase));
+            if let Some(base) = base {
+                if stats.median as f64 > base.median as f64 * (1.0 + threshold) {
+                    regressions.push((day.clone(), phase.clone()));
+                }
+            }
+        }
+    }
+    regressions
+}
+
+/// Helper to print a duration given in nanoseconds
+fn millis(nanos: u64) -> String {
+    format!("{:.3}ms", nanos as f64 / 1e6)
+}
+
+/// Benchmark the exercises of the given days
+pub async fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let days = if options.days.is_empty() {
+        CalenderDay::iter().collect()
+    } else {
+        options.days.clone()
+    };
+
+    let source = if options.generated {
+        Source::Generated {
+            seed: options.generator.seed,
+            size: options.generator.size,
+            scale: options.generator.scale,
+        }
+    } else {
+        Source::Files
+    };
+
+    // benchmark each day, a failing day is reported and skipped
+    let mut timings = Timings::new();
+    let mut failures = Vec::new();
+    for day in days {
+        match bench_day(day, &options).await {
+            Ok(phases) => {
+                timings.insert(day.to_string(), phases);
+            }
+            Err(err) => {
+                println!("Day {} failed: {}", day, err);
+                failures.push(day);
+            }
+        }
+    }
+
+    // compare against the baseline, if there is one and it was taken on the same inputs
+    let baseline = match fs::read_to_string(&options.baseline) {
+        Ok(baseline) => {
+            let baseline: Baseline = serde_json::from_str(&baseline).map_err(|err| {
+                format!(
+                    "The baseline {} is invalid: {}",
+                    options.baseline.display(),
+                    err
+                )
+            })?;
+            if baseline.source == source {
+                baseline.timings
+            } else {
+                println!(
+                    "The baseline was taken on {:?}, not on {:?}, so it is not compared",
+                    baseline.source, source
+                );
+                Timings::new()
+            }
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Timings::new(),
+        Err(err) => {
+            return Err(format!(
+                "The baseline {} can't be read: {}",
+                options.baseline.display(),
+                err
+            )
+            .into())
+        }
+    };
+    let regressions = regressions(&timings, &baseline, options.threshold);
+
+    println!(
+        "{:<4} {:<6} {:>12} {:>12} {:>12} {:>12} {:>8}",
+        "day", "phase", "min", "median", "p95", "baseline", "change"
+    );
+    for (day, phases) in &timings {
+        for (phase, stats) in phases {
+            let (base, change) = match baseline.get(day).and_then(|phases| phases.get(phase)) {
+                Some(base) => (
+                    millis(base.median),
+                    format!(
+                        "{:+.1}%",
+                        (stats.median as f64 / base.median as f64 - 1.0) * 100.0
+                    ),
+                ),
+                None => Default::default(),
+            };
+            let flag = if regressions.contains(&(day.clone(), phase.clone())) {
+                " REGRESSION"
+            } else {
+                ""
+            };
+            println!(
+                "{:<4} {:<6} {:>12} {:>12} {:>12} {:>12} {:>8}{}",
+                day,
+                phase,
+                millis(stats.min),
+                millis(stats.median),
+                millis(stats.p95),
+                base,
+                change,
+                flag
+            );
+        }
+    }
+
+    if options.save {
+        let baseline = Baseline { source, timings };
+        fs::write(&options.baseline, serde_json::to_string_pretty(&baseline)?)?;
+        println!("Saved baseline to {}", options.baseline.display());
+    }
+
+    if !failures.is_empty() {
+        let days: Vec<_> = failures.iter().map(ToString::to_string).collect();
+        Err(format!("The days {} failed", days.join(", ")).into())
+    } else if !regressions.is_empty() {
+        Err(format!(
+            "{} phases regressed by more than {:.0}%",
+            regressions.len(),
+            options.threshold * 100.0
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Benchmark the exercise of a single day. Returns the statistics of each phase.
+async fn bench_day(
+    day: CalenderDay,
+    options: &Options,
+) -> Result<BTreeMap<String, Statistics>, Box<dyn Error>> {
+    let mut phases: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+    // generate the input once, or read it from the input file on each run
+    let generated = if options.generated {
+        let mut input = Vec::new();
+        let mut rng = ChaCha8Rng::seed_from_u64(options.generator.seed);
+        day.generate(&mut rng, &options.generator, &mut input)?;
+        Some(String::from_utf8(input)?)
+    } else {
+        None
+    };
+
+    for _ in 0..options.runs.max(1) {
+        let input = match &generated {
+            Some(input) => Input::from(input.as_str()),
+            None => {
+                let start = Instant::now();
+                let input = Input::read(options.inputs.join(day.input_path())).await?;
+                phases.entry("load").or_default().push(start.elapsed());
+                input
+            }
+        };
+
+        let start = Instant::now();
+        day.execute(input, &advent::Options::default()).await?;
+        phases.entry("solve").or_default().push(start.elapsed());
+    }
+
+    Ok(phases
+        .into_iter()
+        .map(|(phase, t)| (phase.to_owned(), Statistics::new(&t)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Helper to build statistics with the given median
+    fn median(median: u64) -> Statistics {
+        Statistics {
+            min: median,
+            median,
+            p95: median,
+        }
+    }
+
+    #[test]
+    fn statistics() {
+        let timings: Vec<_> = (1..=20).rev().map(Duration::from_nanos).collect();
+        assert_eq!(
+            Statistics::new(&timings),
+            Statistics {
+                min: 1,
+                median: 11,
+                p95: 19
+            }
+        );
+        assert_eq!(
+            Statistics::new(&[Duration::from_nanos(7)]),
+            Statistics {
+                min: 7,
+                median: 7,
+                p95: 7
+            }
+        );
+    }
+
+    #[test]
+    fn baseline_keeps_its_source() {
+        let baseline = Baseline {
+            source: Source::Generated {
+                seed: 1,
+                size: 30,
+                scale: None,
+            },
+            timings: Timings::from([(
+                "01".to_owned(),
+                BTreeMap::from([("solve".to_owned(), median(100))]),
+            )]),
+        };
+        let json = serde_json::to_string(&baseline).unwrap();
+        let parsed: Baseline = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.source, baseline.source);
+        assert_eq!(parsed.timings, baseline.timings);
+        assert_ne!(parsed.source, Source::Files);
+    }
+
+    #[tokio::test]
+    async fn failing_days_and_baselines() {
+        let dir = std::env::temp_dir().join(format!("aoc2021-bench-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let options = |days: &[&str], baseline: &str| {
+            let mut args = vec!["bench", "--runs", "1", "--inputs"];
+            args.push(dir.to_str().unwrap());
+            args.extend(["--baseline", baseline]);
+            args.extend(days);
+            Options::from_iter(args)
+        };
+
+        // the input file of day 02 is invalid, but day 01 is still benchmarked
+        fs::write(dir.join("input01.txt"), "1\n2\n").unwrap();
+        fs::write(dir.join("input02.txt"), "sideways 1\n").unwrap();
+        let baseline = dir.join("baseline.json");
+        let error = run(options(&["01", "02"], baseline.to_str().unwrap()))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "The days 02 failed");
+
+        // an unreadable baseline is an error, unlike a missing one
+        fs::write(&baseline, "{").unwrap();
+        let error = run(options(&["01"], baseline.to_str().unwrap()))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("The baseline"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn flags_regressions_above_threshold() {
+        let baseline = Timings::from([(
+            "01".to_owned(),
+            BTreeMap::from([
+                ("load".to_owned(), median(100)),
+                ("solve".to_owned(), median(100)),
+            ]),
+        )]);
+        let timings = Timings::from([
+            (
+                "01".to_owned(),
+                BTreeMap::from([
+                    ("load".to_owned(), median(110)),
+                    ("solve".to_owned(), median(111)),
+                ]),
+            ),
+            (
+                "02".to_owned(),
+                BTreeMap::from([("solve".to_owned(), median(500))]),
+            ),
+        ]);
+
+        assert_eq!(
+            regressions(&timings, &baseline, 0.1),
+            [("01".to_owned(), "solve".to_owned())]
+        );
+    }
+}
@@ -1,31 +1,123 @@
-use std::{error::Error, path::PathBuf, str::FromStr};
-use structopt::{clap::AppSettings::*, StructOpt};
-use tokio::{fs::File, io::BufReader};
-use tokio_util::codec::{FramedRead, LinesCodec};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::{
+    error::Error,
+    fs,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process,
+    str::FromStr,
+};
+use structopt::{
+    clap::{self, AppSettings::*},
+    StructOpt,
+};
 
 mod advent;
+mod bench;
 
 #[derive(Debug, StructOpt)]
-#[structopt(author, about, setting(ColoredHelp))]
+#[structopt(author, about, setting(ColoredHelp), setting(ArgRequiredElseHelp))]
 struct Cli {
+    /// The day of the calender to execute
+    day: Option<advent::CalenderDay>,
+
+    /// Stream the input file line by line instead of reading it into memory at once
+    #[structopt(long)]
+    stream: bool,
+
+    #[structopt(flatten)]
+    options: advent::Options,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Generate a random, but valid puzzle input for the given day
+    Generate {
+        /// The day of the calender to generate the input for
+        day: advent::CalenderDay,
+
+        #[structopt(flatten)]
+        options: advent::GeneratorOptions,
+
+        /// Write the generated input to this file instead of stdout
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Run the exercises several times and compare their timings against a saved baseline
+    Bench(bench::Options),
+
+    /// Scramble the wires of a display and show some symbols on it, like the displays of
+    /// day 08
+    Scramble(advent::ScrambleOptions),
+}
+
+/// Generate a random puzzle input for the given day
+fn generate(
     day: advent::CalenderDay,
+    options: advent::GeneratorOptions,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    // the same seed always has to result in the same input
+    let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+
+    // write the input either to the given file, or to stdout
+    let mut output: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    day.generate(&mut rng, &options, &mut output)?;
+    output.flush()?;
+
+    Ok(())
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    // parse the command line commands
-    let cli = Cli::from_args();
-    let day = cli.day;
+async fn main() {
+    // print errors with their message instead of their debug representation
+    if let Err(err) = run(Cli::from_args()).await {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
 
-    // Open the input file
-    let path = PathBuf::from_str("./inputs")?.join(day.input_path());
-    let file = BufReader::new(File::open(path).await?);
+/// Run the command or the exercise of the day given on the command line
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let day = match cli.command {
+        Some(Command::Generate {
+            day,
+            options,
+            output,
+        }) => return generate(day, options, output),
+        Some(Command::Bench(options)) => return bench::run(options).await,
+        Some(Command::Scramble(options)) => {
+            println!("{}", advent::scramble(&options)?);
+            return Ok(());
+        }
+        None => match cli.day {
+            Some(day) => day,
+            None => clap::Error::with_description(
+                "The day to execute is missing, see --help for usage",
+                clap::ErrorKind::MissingRequiredArgument,
+            )
+            .exit(),
+        },
+    };
 
-    // Wrap input file into a lines codec to extract each line
-    let input = FramedRead::new(file, LinesCodec::new());
+    // Read the input file, or open it to stream it
+    let path = PathBuf::from_str("./inputs")?.join(day.input_path());
+    let input = if cli.stream {
+        advent::Input::stream(path).await?
+    } else {
+        advent::Input::read(path).await?
+    };
 
     // run the exercise of the day and print the results
-    let res = day.execute(input).await?;
+    let res = day.execute(input, &cli.options).await?;
 
     // print result
     println!("Result of day {}:", day);